@@ -0,0 +1,146 @@
+#ifndef DUNGEON_H
+#define DUNGEON_H
+
+/* Generated from src/ffi.rs by cbindgen; don't edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#define DUNGEON_ALGORITHM_ROOMS 0
+
+#define DUNGEON_ALGORITHM_BSP 1
+
+#define DUNGEON_TILE_EMPTY 0
+
+#define DUNGEON_TILE_FLOOR 1
+
+#define DUNGEON_TILE_WALL 2
+
+#define DUNGEON_TILE_STAIRS_UP 3
+
+#define DUNGEON_TILE_STAIRS_DOWN 4
+
+#define DUNGEON_TILE_DOOR 5
+
+#define DUNGEON_TILE_SECRET_DOOR 6
+
+#define DUNGEON_ROLE_NORMAL 0
+
+#define DUNGEON_ROLE_ENTRANCE 1
+
+#define DUNGEON_ROLE_BOSS 2
+
+#define DUNGEON_ROLE_TREASURE 3
+
+#define DUNGEON_ROLE_SECRET 4
+
+// A generated level.
+typedef struct DungeonLevel DungeonLevel;
+
+// Settings for a level, starting from the command line's defaults.
+typedef struct DungeonParams DungeonParams;
+
+// A room's position and size in tiles, and its role if roles are turned on.
+typedef struct DungeonRoom {
+  int x;
+  int y;
+  int width;
+  int height;
+  int role;
+} DungeonRoom;
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Why the last call to `dungeon_generate` on this thread failed, or null if it succeeded
+// or none has been made. The string belongs to the library and lasts until the next
+// call to `dungeon_generate`.
+const char *dungeon_last_error(void);
+
+// New settings, to be freed with `dungeon_params_free`.
+struct DungeonParams *dungeon_params_new(void);
+
+// # Safety
+// `params` must come from `dungeon_params_new` and not have been freed, or be null.
+void dungeon_params_free(struct DungeonParams *params);
+
+// Any text or an existing seed, as with `--seed`. A random seed is used if unset or null.
+//
+// # Safety
+// `params` must be valid settings, and `seed` a nul-terminated UTF-8 string or null.
+void dungeon_params_set_seed(struct DungeonParams *params, const char *seed);
+
+// # Safety
+// `params` must be valid settings.
+void dungeon_params_set_size(struct DungeonParams *params, int width, int height);
+
+// # Safety
+// `params` must be valid settings.
+void dungeon_params_set_min_room_size(struct DungeonParams *params, int width, int height);
+
+// One of the `DUNGEON_ALGORITHM_` constants. Returns false, leaving the setting as it
+// was, for anything else.
+//
+// # Safety
+// `params` must be valid settings.
+bool dungeon_params_set_algorithm(struct DungeonParams *params, int algorithm);
+
+// # Safety
+// `params` must be valid settings.
+void dungeon_params_set_walls(struct DungeonParams *params, bool walls);
+
+// # Safety
+// `params` must be valid settings.
+void dungeon_params_set_roles(struct DungeonParams *params, bool roles);
+
+// Generates the first floor with the settings, returning null if they're invalid; see
+// `dungeon_last_error`, which is cleared at the start of each call. Levels over 4096
+// tiles wide or high, and minimum rooms bigger than the level, are turned down before
+// anything is generated. The level is freed with `dungeon_level_free`.
+//
+// # Safety
+// `params` must be valid settings.
+struct DungeonLevel *dungeon_generate(const struct DungeonParams *params);
+
+// # Safety
+// `level` must come from `dungeon_generate` and not have been freed, or be null.
+void dungeon_level_free(struct DungeonLevel *level);
+
+// The seed the level was made from, to make it again. Lasts as long as the level.
+//
+// # Safety
+// `level` must be a valid level.
+const char *dungeon_level_seed(const struct DungeonLevel *level);
+
+// # Safety
+// `level` must be a valid level.
+int dungeon_level_width(const struct DungeonLevel *level);
+
+// # Safety
+// `level` must be a valid level.
+int dungeon_level_height(const struct DungeonLevel *level);
+
+// One of the `DUNGEON_TILE_` constants, or -1 if the point is outside the level.
+//
+// # Safety
+// `level` must be a valid level.
+int dungeon_level_tile(const struct DungeonLevel *level, int x, int y);
+
+// # Safety
+// `level` must be a valid level.
+size_t dungeon_level_room_count(const struct DungeonLevel *level);
+
+// Copies the room at `index` into `room`, returning false if there's no such room.
+//
+// # Safety
+// `level` must be a valid level and `room` must point to a `DungeonRoom`.
+bool dungeon_level_room(const struct DungeonLevel *level, size_t index, struct DungeonRoom *room);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DUNGEON_H */
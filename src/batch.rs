@@ -0,0 +1,267 @@
+use serde_derive::Serialize;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::thread;
+
+use crate::builder::DungeonBuilder;
+use crate::config::Config;
+use crate::draw::save;
+use crate::dungeon::Dungeon;
+use crate::level::LevelStats;
+use crate::output::{FormatOptions, OutputFormat};
+use crate::seed::Seed;
+use crate::theme::Theme;
+
+// written to manifest.json alongside the levels
+#[derive(Serialize)]
+pub struct Manifest {
+    pub seed: String,
+    pub config: Config,
+    pub formats: Vec<OutputFormat>,
+    pub levels: Vec<BatchEntry>,
+}
+
+impl Manifest {
+    pub fn failed(&self) -> usize {
+        self.levels
+            .iter()
+            .filter(|entry| entry.error.is_some())
+            .count()
+    }
+}
+
+#[derive(Serialize)]
+pub struct BatchEntry {
+    pub index: usize,
+    pub seed: String,
+    // relative to the output directory
+    pub files: Vec<String>,
+    // one for each floor
+    pub stats: Vec<LevelStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Creates `count` levels (or dungeons, if the config has more than one floor) using seeds
+// derived from `seed`, so level n of a batch can be recreated with `seed.derive(n)`.
+// Each is written to `out_dir` in every one of `formats`, and drawn if that's turned on.
+// A level which fails is noted in the manifest and the rest carry on.
+pub fn run_batch(
+    config: &Config,
+    formats: &[OutputFormat],
+    seed: &Seed,
+    count: usize,
+    threads: usize,
+    out_dir: &str,
+) -> std::io::Result<Manifest> {
+    fs::create_dir_all(out_dir)?;
+    let builder = config.builder()?;
+    let theme = config.theme()?;
+    let options = config.format_options()?;
+    let threads = threads.max(1);
+
+    let mut levels: Vec<BatchEntry> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|worker| {
+                let (builder, theme, options) = (&builder, &theme, &options);
+                scope.spawn(move || {
+                    (worker..count)
+                        .step_by(threads)
+                        .map(|index| {
+                            create_entry(
+                                config, formats, builder, theme, options, seed, index, out_dir,
+                            )
+                        })
+                        .collect::<Vec<BatchEntry>>()
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Batch thread failed"))
+            .collect()
+    });
+    levels.sort_by_key(|entry| entry.index);
+
+    let manifest = Manifest {
+        seed: seed.to_string(),
+        config: config.clone(),
+        formats: formats.to_vec(),
+        levels,
+    };
+    let serialised = serde_json::to_string_pretty(&manifest)?;
+    fs::write(Path::new(out_dir).join("manifest.json"), serialised)?;
+
+    Ok(manifest)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_entry(
+    config: &Config,
+    formats: &[OutputFormat],
+    builder: &DungeonBuilder,
+    theme: &Theme,
+    options: &FormatOptions,
+    seed: &Seed,
+    index: usize,
+    out_dir: &str,
+) -> BatchEntry {
+    let seed = seed.derive(index);
+    let mut entry = BatchEntry {
+        index,
+        seed: seed.to_string(),
+        files: vec![],
+        stats: vec![],
+        error: None,
+    };
+
+    // a panic in one level shouldn't stop the batch
+    let built = panic::catch_unwind(AssertUnwindSafe(|| {
+        builder.clone().seed(seed.clone()).build_dungeon()
+    }));
+    let dungeon = match built {
+        Ok(Ok(dungeon)) => dungeon,
+        Ok(Err(err)) => {
+            entry.error = Some(err.to_string());
+            return entry;
+        }
+        Err(payload) => {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_else(|| "Generation panicked".to_string()),
+            };
+            entry.error = Some(message);
+            return entry;
+        }
+    };
+
+    entry.stats = dungeon.floors.iter().map(|level| level.stats()).collect();
+    if let Err(err) = write_files(
+        config,
+        formats,
+        theme,
+        options,
+        &dungeon,
+        &seed,
+        out_dir,
+        &mut entry.files,
+    ) {
+        entry.error = Some(err.to_string());
+    }
+
+    entry
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_files(
+    config: &Config,
+    formats: &[OutputFormat],
+    theme: &Theme,
+    options: &FormatOptions,
+    dungeon: &Dungeon,
+    seed: &Seed,
+    out_dir: &str,
+    files: &mut Vec<String>,
+) -> std::io::Result<()> {
+    for format in formats {
+        let name = format!("{}.{}", seed, format.extension());
+        fs::write(
+            Path::new(out_dir).join(&name),
+            format.dungeon(dungeon, options)?,
+        )?;
+        files.push(name);
+    }
+
+    if config.draw {
+        for (floor, level) in dungeon.floors.iter().enumerate() {
+            let name = match dungeon.floors.len() {
+                1 => seed.to_string(),
+                _ => format!("{}-{}", seed, floor),
+            };
+            let name = format!("{}.png", name);
+            save(level, theme, &Path::new(out_dir).join(&name))?;
+            files.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::batch::run_batch;
+    use crate::config::Config;
+    use crate::output::OutputFormat;
+    use crate::seed::Seed;
+
+    // an empty directory for one test, so files left by an earlier run can't be mistaken
+    // for ones the test wrote
+    fn empty_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dungeon-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_batch() {
+        let dir = empty_dir("test-batch");
+        let out_dir = dir.to_str().unwrap();
+
+        let formats = [OutputFormat::Csv, OutputFormat::Json];
+        let seed = Seed::new("batch");
+        let manifest = run_batch(&Config::default(), &formats, &seed, 5, 2, out_dir).unwrap();
+
+        assert_eq!(manifest.failed(), 0);
+        assert_eq!(manifest.levels.len(), 5);
+        for (index, entry) in manifest.levels.iter().enumerate() {
+            assert_eq!(entry.index, index);
+            assert_eq!(entry.seed, seed.derive(index).to_string());
+            assert_eq!(
+                entry.files,
+                vec![
+                    format!("{}.csv", entry.seed),
+                    format!("{}.json", entry.seed)
+                ]
+            );
+            assert!(entry.files.iter().all(|file| dir.join(file).exists()));
+        }
+        assert!(dir.join("manifest.json").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_batch_failures() {
+        let dir = empty_dir("test-batch-failures");
+        let config = Config {
+            width: 0,
+            ..Config::default()
+        };
+        let formats = [config.format];
+        let manifest = run_batch(
+            &config,
+            &formats,
+            &Seed::new("fail"),
+            3,
+            1,
+            dir.to_str().unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(manifest.failed(), 3);
+        assert!(manifest.levels.iter().all(|entry| entry.files.is_empty()));
+        // only the manifest
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,210 +1,626 @@
-use arrayref::array_ref;
-use dungeon::{bsp, draw, roomscorridors};
+use dungeon::{batch, config, csv, draw, import, output, record, seed, svg};
 
-use clap::{Arg, Command};
-use rand::distributions::Alphanumeric;
-use rand::prelude::*;
-use sha2::{Digest, Sha256};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use draw::draw;
-
-use bsp::BspLevel;
-use roomscorridors::RoomsCorridors;
-
-fn create_hash(text: &str) -> String {
-    let mut hasher = Sha256::default();
-    hasher.update(text.as_bytes());
-    format!("{:x}", hasher.finalize())
-}
-
-enum Algorithm {
-    Bsp,
-    Rooms,
-}
+use batch::run_batch;
+use config::Config;
+use draw::save;
+use dungeon::dungeon::{Algorithm, Dungeon};
+use dungeon::level::TILE_SIZE;
+use import::{load_board, Palette};
+use output::OutputFormat;
+use record::{save_frames, save_gif};
+use seed::Seed;
+use svg::to_svg;
 
 fn main() {
-    // config:
-    // hash (pass hash directly)
-    // text (hashed + used)
-    // width
-    // height
-    // max rooms
-    // room size
-    let matches = Command::new("Dungeon")
+    // settings come from the config file if there is one, then flags override them
+    let command = Command::new("Dungeon")
         .version("3.0")
         .author("James Baum <@whostolemyhat>")
+        .arg(
+            Arg::new("config")
+                .global(true)
+                .long("config")
+                .help("Load settings from this JSON file; flags override them"),
+        )
+        .arg(
+            Arg::new("dumpconfig")
+                .global(true)
+                .long("dump-config")
+                .action(ArgAction::SetTrue)
+                .help("Print the settings which would be used as JSON, without creating a level"),
+        )
         .arg(
             Arg::new("text")
+                .global(true)
                 .short('t')
                 .long("text")
                 .help("A string to hash and use as a seed"),
         )
         .arg(
             Arg::new("seed")
+                .global(true)
                 .short('s')
                 .long("seed")
-                .help("An existing seed. Must be 32 characters"),
+                .help("An existing seed, or any text or number to use as one"),
         )
         .arg(
             Arg::new("algo")
+                .global(true)
                 .short('a')
                 .long("algorithm")
                 .value_parser(["rooms", "bsp"])
-                .default_value("rooms")
-                .help("The type of procedural algorithm to use"),
+                .help("The type of procedural algorithm to use [default: rooms]"),
         )
         .arg(
             Arg::new("json")
+                .global(true)
                 .short('j')
                 .long("json")
-                .num_args(0)
-                .help("If set, displays serialised JSON output"),
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["csv", "format"])
+                .help("Short for --output-format json"),
         )
         .arg(
             Arg::new("draw")
+                .global(true)
                 .short('d')
                 .long("draw")
-                .num_args(0)
+                .action(ArgAction::SetTrue)
                 .help("If set, creates a png representation"),
         )
+        .arg(
+            negation("nodraw", "no-draw", "draw")
+                .help("Don't create a png, even if the config file does"),
+        )
+        .arg(
+            Arg::new("image")
+                .global(true)
+                .long("image")
+                .help("Draw the level to this png file [default: img/<seed>.png]"),
+        )
+        .arg(
+            Arg::new("theme")
+                .global(true)
+                .long("theme")
+                .help("Colours for png and svg output: classic, parchment, dark, blueprint or a theme file"),
+        )
+        .arg(
+            Arg::new("tilesize")
+                .global(true)
+                .long("tile-size")
+                .help("Pixels per tile in png and svg output [default: 16]"),
+        )
+        .arg(
+            Arg::new("svg")
+                .global(true)
+                .long("svg")
+                .help("Draw the level to this svg file"),
+        )
+        .arg(
+            Arg::new("svgshow")
+                .global(true)
+                .long("svg-show")
+                .value_delimiter(',')
+                .value_parser(["outlines", "ids", "grid", "markers"])
+                .help("Extra layers to add to the svg: outlines, ids, grid, markers"),
+        )
+        .arg(
+            Arg::new("record")
+                .global(true)
+                .long("record")
+                .help("Record each generation step to this gif, or as numbered pngs in this directory"),
+        )
+        .arg(
+            Arg::new("import")
+                .global(true)
+                .long("import")
+                .help("Read the level from this png or csv instead of generating one, then run the other passes on it"),
+        )
+        .arg(
+            Arg::new("palette")
+                .global(true)
+                .long("palette")
+                .help("Palette file mapping colours in --import to tiles [default: the theme's colours]"),
+        )
         .arg(
             Arg::new("csv")
+                .global(true)
                 .short('c')
                 .long("csv")
-                .num_args(0)
-                .help("Output board in CSV format"),
+                .action(ArgAction::SetTrue)
+                .conflicts_with("format")
+                .help("Short for --output-format csv"),
+        )
+        .arg(
+            Arg::new("glyphs")
+                .global(true)
+                .long("glyphs")
+                .help("Draw text output with these characters: classic, box, digits or a glyph file"),
+        )
+        .arg(
+            Arg::new("colour")
+                .global(true)
+                .long("colour")
+                .action(ArgAction::SetTrue)
+                .help("Colour text drawn with --glyphs using the theme"),
+        )
+        .arg(
+            negation("nocolour", "no-colour", "colour")
+                .help("Don't colour text, even if the config file does"),
+        )
+        .arg(
+            Arg::new("csvdelimiter")
+                .global(true)
+                .long("csv-delimiter")
+                .help("Character between tiles in csv output and import [default: ,]"),
+        )
+        .arg(
+            Arg::new("format")
+                .global(true)
+                .long("output-format")
+                .value_parser(["text", "json", "csv"])
+                .action(ArgAction::Append)
+                .help("Format to print the level in, or with batch to write each level in; repeat to give batch several [default: text]"),
+        )
+        .arg(
+            Arg::new("output")
+                .global(true)
+                .long("output")
+                .help("Write the level to this file instead of printing it"),
         )
         .arg(
             Arg::new("walls")
+                .global(true)
                 .short('w')
                 .long("walls")
-                .num_args(0)
+                .action(ArgAction::SetTrue)
                 .help("Add wall tile around rooms"),
         )
+        .arg(
+            negation("nowalls", "no-walls", "walls")
+                .help("Don't add walls, even if the config file does"),
+        )
         .arg(
             Arg::new("height")
+                .global(true)
                 .short('y')
-                .default_value("40")
                 .long("height")
-                .help("Height of the level"),
+                .help("Height of the level [default: 40]"),
         )
         .arg(
             Arg::new("width")
+                .global(true)
                 .short('x')
                 .long("width")
-                .default_value("48")
-                .help("Width of the level"),
+                .help("Width of the level [default: 48]"),
         )
         .arg(
             Arg::new("minroomwidth")
+                .global(true)
                 .short('m')
                 .long("minroomwidth")
-                .default_value("4")
-                .help("Minimum width of rooms"),
+                .help("Minimum width of rooms [default: 4]"),
         )
         .arg(
             Arg::new("minroomheight")
+                .global(true)
                 .short('n')
                 .long("minroomheight")
-                .default_value("5")
-                .help("Minimum height of rooms"),
-        )
-        .get_matches();
-
-    let board_width = matches
-        .get_one::<String>("width")
-        .expect("Width not set")
-        .parse::<i32>()
-        .expect("Couldn't parse width");
-    let board_height = matches
-        .get_one::<String>("height")
-        .expect("Height not set")
-        .parse::<i32>()
-        .expect("Couldn't parse height");
-
-    let seed: String = match matches.get_one::<String>("seed") {
-        Some(text) => {
-            if text.chars().count() < 32 {
-                panic!("Seed must be 32 characters long. Use -t option to create a new seed.")
-            }
-            text.to_string()
-        }
-        None => match matches.get_one::<String>("text") {
-            Some(text) => create_hash(text),
-            None => create_hash(
-                &thread_rng()
-                    .sample_iter(&Alphanumeric)
-                    .take(32)
-                    .collect::<String>(),
+                .help("Minimum height of rooms [default: 5]"),
+        )
+        .arg(
+            Arg::new("prune")
+                .global(true)
+                .short('p')
+                .long("prune")
+                .num_args(0..=1)
+                .default_missing_value("0")
+                .help("Remove corridor dead ends, keeping the given fraction of them (0.0 - 1.0)"),
+        )
+        .arg(
+            Arg::new("floors")
+                .global(true)
+                .short('f')
+                .long("floors")
+                .help("Number of floors, linked by stairs [default: 1]"),
+        )
+        .arg(
+            Arg::new("populate")
+                .global(true)
+                .short('e')
+                .long("populate")
+                .help("Place entities using the spawn rules in this JSON file, eg tables/default.json"),
+        )
+        .arg(
+            Arg::new("locks")
+                .global(true)
+                .short('l')
+                .long("locks")
+                .help("Number of locked doors to add, each with a key placed before it"),
+        )
+        .arg(
+            Arg::new("roles")
+                .global(true)
+                .short('r')
+                .long("roles")
+                .action(ArgAction::SetTrue)
+                .help("Tag rooms as entrance, boss, treasure or secret"),
+        )
+        .arg(
+            negation("noroles", "no-roles", "roles")
+                .help("Don't tag rooms, even if the config file does"),
+        )
+        .arg(
+            Arg::new("tree")
+                .global(true)
+                .long("tree")
+                .action(ArgAction::SetTrue)
+                .help("Keep the BSP partition tree, adding it to JSON output and drawing it over images"),
+        )
+        .arg(
+            negation("notree", "no-tree", "tree")
+                .help("Don't keep the BSP partition tree, even if the config file does"),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Create levels from seeds derived from one, writing them to a directory")
+                .arg(
+                    Arg::new("count")
+                        .long("count")
+                        .required(true)
+                        .help("Number of levels to create"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .default_value("1")
+                        .help("Number of levels to create at once"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .short('o')
+                        .long("out")
+                        .default_value("batch")
+                        .help("Directory to write levels and manifest.json to"),
+                ),
+        );
+    #[cfg(feature = "serve")]
+    let command = command.subcommand(
+        Command::new("serve")
+            .about("Serve levels over HTTP at /level, using these settings as the defaults")
+            .arg(
+                Arg::new("address")
+                    .long("address")
+                    .default_value("127.0.0.1:8080")
+                    .help("Address to listen on"),
             ),
-        },
-    };
+    );
+    #[cfg(feature = "view")]
+    let command = command.subcommand(
+        Command::new("view")
+            .about("Show the level in the terminal, changing settings and seeds with keys"),
+    );
+    let matches = command.get_matches();
 
-    let walls = matches.contains_id("walls");
-    let method = match matches
-        .get_one::<String>("algo")
-        .expect("Default algorithm not set")
-        .as_str()
-    {
-        "bsp" => Algorithm::Bsp,
-        "rooms" => Algorithm::Rooms,
-        _ => unreachable![],
+    let mut config = match matches.get_one::<String>("config") {
+        Some(path) => Config::load(path).expect("Couldn't load config"),
+        None => Config::default(),
     };
 
-    let min_room_width: i32 = matches
-        .get_one::<String>("minroomwidth")
-        .expect("No room width")
-        .parse::<i32>()
-        .expect("Couldn't parse room width");
-    let min_room_height: i32 = matches
-        .get_one::<String>("minroomheight")
-        .expect("No room height")
-        .parse::<i32>()
-        .expect("Couldn't parse room height");
-
-    let seed_u8 = array_ref!(seed.as_bytes(), 0, 32);
-    let mut rng: StdRng = SeedableRng::from_seed(*seed_u8);
-
-    let level = match method {
-        Algorithm::Rooms => RoomsCorridors::create(
-            board_width,
-            board_height,
-            &seed,
-            &mut rng,
-            walls,
-            min_room_width,
-            min_room_height,
-        ),
-        Algorithm::Bsp => BspLevel::create(
-            board_width,
-            board_height,
-            &seed,
-            &mut rng,
-            walls,
-            min_room_width,
-            min_room_height,
-        ),
+    if let Some(seed) = matches.get_one::<String>("seed") {
+        config.seed = Some(seed.to_string());
+        config.text = None;
+    } else if let Some(text) = matches.get_one::<String>("text") {
+        config.seed = None;
+        config.text = Some(text.to_string());
+    }
+
+    if let Some(algo) = matches.get_one::<String>("algo") {
+        config.algorithm = match algo.as_str() {
+            "bsp" => Algorithm::Bsp,
+            "rooms" => Algorithm::Rooms,
+            _ => unreachable![],
+        };
+    }
+
+    if let Some(width) = parse(&matches, "width", "Couldn't parse width") {
+        config.width = width;
+    }
+    if let Some(height) = parse(&matches, "height", "Couldn't parse height") {
+        config.height = height;
+    }
+    if let Some(width) = parse(&matches, "minroomwidth", "Couldn't parse room width") {
+        config.min_room_width = width;
+    }
+    if let Some(height) = parse(&matches, "minroomheight", "Couldn't parse room height") {
+        config.min_room_height = height;
+    }
+    if let Some(floors) = parse(&matches, "floors", "Couldn't parse floors") {
+        config.floors = floors;
+    }
+    if let Some(keep) = parse(&matches, "prune", "Couldn't parse prune fraction") {
+        config.prune = Some(keep);
+    }
+    if let Some(count) = parse(&matches, "locks", "Couldn't parse locks") {
+        config.locks = count;
+    }
+    if let Some(path) = matches.get_one::<String>("populate") {
+        config.populate = Some(path.to_string());
+    }
+
+    if let Some(walls) = toggle(&matches, "walls", "nowalls") {
+        config.walls = walls;
+    }
+    if let Some(roles) = toggle(&matches, "roles", "noroles") {
+        config.roles = roles;
+    }
+    if let Some(tree) = toggle(&matches, "tree", "notree") {
+        config.tree = tree;
+    }
+    let draw = toggle(&matches, "draw", "nodraw");
+    if let Some(draw) = draw {
+        config.draw = draw;
+    }
+    if let Some(path) = matches.get_one::<String>("image") {
+        config.image = Some(path.to_string());
+        // --image implies --draw unless --no-draw was given
+        if draw.is_none() {
+            config.draw = true;
+        }
+    }
+    if let Some(theme) = matches.get_one::<String>("theme") {
+        config.theme = Some(theme.to_string());
+    }
+    if let Some(size) = parse(&matches, "tilesize", "Couldn't parse tile size") {
+        config.tile_size = Some(size);
+    }
+    if let Some(path) = matches.get_one::<String>("svg") {
+        config.svg = Some(path.to_string());
+    }
+    if let Some(path) = matches.get_one::<String>("record") {
+        config.record = Some(path.to_string());
+    }
+    if let Some(path) = matches.get_one::<String>("import") {
+        config.import = Some(path.to_string());
+    }
+    if let Some(path) = matches.get_one::<String>("palette") {
+        config.palette = Some(path.to_string());
+    }
+    if let Some(layers) = matches.get_many::<String>("svgshow") {
+        for layer in layers {
+            match layer.as_str() {
+                "outlines" => config.svg_options.outlines = true,
+                "ids" => config.svg_options.room_ids = true,
+                "grid" => config.svg_options.grid = true,
+                "markers" => config.svg_options.markers = true,
+                _ => unreachable![],
+            }
+        }
+    }
+
+    // batch can write each level in several formats, but everything else prints one
+    let mut formats: Vec<OutputFormat> = vec![];
+    let given = matches.get_many::<String>("format").into_iter().flatten();
+    for format in given {
+        let format = match format.as_str() {
+            "text" => OutputFormat::Text,
+            "json" => OutputFormat::Json,
+            "csv" => OutputFormat::Csv,
+            _ => unreachable![],
+        };
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    if matches.get_flag("json") {
+        formats.push(OutputFormat::Json);
+    }
+    if matches.get_flag("csv") {
+        formats.push(OutputFormat::Csv);
+    }
+    if formats.len() > 1 && matches.subcommand_matches("batch").is_none() {
+        panic!("Only batch can write more than one output format");
+    }
+    match formats.first() {
+        Some(&format) => config.format = format,
+        None => formats.push(config.format),
+    }
+    if let Some(delimiter) = parse(&matches, "csvdelimiter", "Delimiter must be one character") {
+        config.csv_options.delimiter = delimiter;
+    }
+    if let Some(glyphs) = matches.get_one::<String>("glyphs") {
+        config.glyphs = Some(glyphs.to_string());
+    }
+    if let Some(colour) = toggle(&matches, "colour", "nocolour") {
+        config.colour = colour;
+    }
+    if let Some(path) = matches.get_one::<String>("output") {
+        config.output = Some(path.to_string());
+    }
+
+    if matches.get_flag("dumpconfig") {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&config).expect("Serialising config failed")
+        );
+        return;
+    }
+
+    // each request picks its own seed unless one is set here
+    #[cfg(feature = "serve")]
+    if let Some(serve) = matches.subcommand_matches("serve") {
+        let address = serve.get_one::<String>("address").expect("Address not set");
+        dungeon::serve::serve(&config, address).expect("Couldn't run server");
+        return;
+    }
+
+    // only the level goes to stdout, so it can be piped elsewhere
+    let seed = config.seed().unwrap_or_else(Seed::random);
+    eprintln!("Seed: {}", seed);
+
+    if let Some(batch) = matches.subcommand_matches("batch") {
+        let count = parse(batch, "count", "Couldn't parse count").expect("Count not set");
+        let threads = parse(batch, "threads", "Couldn't parse threads").expect("Threads not set");
+        let out_dir = batch
+            .get_one::<String>("out")
+            .expect("Output directory not set");
+
+        let manifest = run_batch(&config, &formats, &seed, count, threads, out_dir)
+            .expect("Couldn't run batch");
+        eprintln!(
+            "Created {} levels in {} from seed {}, {} failed",
+            manifest.levels.len() - manifest.failed(),
+            out_dir,
+            seed,
+            manifest.failed()
+        );
+        return;
+    }
+
+    #[cfg(feature = "view")]
+    if matches.subcommand_matches("view").is_some() {
+        dungeon::view::run(config, seed).expect("Couldn't run viewer");
+        return;
+    }
+
+    let theme = config.theme().expect("Couldn't load theme");
+    let builder = config
+        .builder()
+        .expect("Couldn't load spawn rules")
+        .seed(seed.clone());
+    let dungeon = match &config.import {
+        Some(path) => {
+            let board = if path.ends_with(".csv") {
+                csv::load_board(path, &config.csv_options)
+            } else {
+                let palette = match &config.palette {
+                    Some(palette) => Palette::load(palette).expect("Couldn't load palette"),
+                    None => Palette::from_theme(&theme, theme.tile_size.unwrap_or(TILE_SIZE))
+                        .expect("Couldn't use the theme as a palette"),
+                };
+                load_board(path, &palette)
+            }
+            .expect("Couldn't import level");
+            let level = builder
+                .import(board)
+                .unwrap_or_else(|err| panic!("Couldn't import level: {}", err));
+
+            Dungeon {
+                hash: level.hash.clone(),
+                floors: vec![level],
+            }
+        }
+        None => builder
+            .build_dungeon()
+            .unwrap_or_else(|err| panic!("Couldn't create dungeon: {}", err)),
     };
 
-    let print_json = matches.contains_id("json");
-    let draw_map = matches.contains_id("draw");
-    let csv = matches.contains_id("csv");
+    let document = config
+        .format
+        .dungeon(
+            &dungeon,
+            &config.format_options().expect("Couldn't load glyphs"),
+        )
+        .expect("Serialising level failed");
+    match &config.output {
+        Some(path) => fs::write(path, document).expect("Couldn't write level"),
+        None => print!("{}", document),
+    }
+
+    if config.draw {
+        let image = match &config.image {
+            Some(path) => PathBuf::from(path),
+            None => Path::new("img").join(format!("{}.png", seed)),
+        };
 
-    println!("{}", level);
-    if print_json {
-        let serialised = serde_json::to_string(&level).expect("Serialising level failed");
-        println!("{}", serialised);
+        for (floor, level) in dungeon.floors.iter().enumerate() {
+            let path = match dungeon.floors.len() {
+                1 => image.clone(),
+                _ => floor_path(&image, floor),
+            };
+            save(level, &theme, &path).expect("Drawing failed");
+        }
+    }
+
+    if let Some(svg) = &config.svg {
+        let svg = PathBuf::from(svg);
+        for (floor, level) in dungeon.floors.iter().enumerate() {
+            let path = match dungeon.floors.len() {
+                1 => svg.clone(),
+                _ => floor_path(&svg, floor),
+            };
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).expect("Couldn't create svg directory");
+            }
+            fs::write(&path, to_svg(level, &config.svg_options, &theme))
+                .expect("Couldn't write svg");
+        }
     }
 
-    if draw_map {
-        draw(&level, "./img", &seed).expect("Drawing failed");
+    // generates the first floor again, keeping each step
+    if let Some(record) = &config.record {
+        let level = config
+            .builder()
+            .expect("Couldn't load spawn rules")
+            .seed(seed.clone())
+            .record()
+            .unwrap_or_else(|err| panic!("Couldn't record level: {}", err));
+
+        let path = Path::new(record);
+        match path.extension() {
+            Some(extension) if extension == "gif" => save_gif(&level, &theme, path),
+            _ => save_frames(&level, &theme, path).map(|_| ()),
+        }
+        .expect("Couldn't record level");
     }
+}
 
-    if csv {
-        println!("{:?}", level.board_to_csv());
+// img/level.png becomes img/level-1.png for the second floor
+fn floor_path(path: &Path, floor: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, floor, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, floor),
+    };
+
+    path.with_file_name(name)
+}
+
+// `--no-walls` and so on, to turn off a setting the config file turns on. Whichever of
+// the pair comes last wins.
+fn negation(id: &'static str, long: &'static str, flag: &'static str) -> Arg {
+    Arg::new(id)
+        .global(true)
+        .long(long)
+        .action(ArgAction::SetTrue)
+        .overrides_with(flag)
+}
+
+// the setting a flag or its negation asks for, or None to keep the config file's
+fn toggle(matches: &ArgMatches, on: &str, off: &str) -> Option<bool> {
+    if matches.get_flag(on) {
+        Some(true)
+    } else if matches.get_flag(off) {
+        Some(false)
+    } else {
+        None
     }
 }
 
+fn parse<T: FromStr>(matches: &ArgMatches, id: &str, error: &str) -> Option<T> {
+    matches
+        .get_one::<String>(id)
+        .map(|value| value.parse::<T>().ok().expect(error))
+}
+
 // include pre-generated rooms
 // add detail to rooms
 // drunkards walk
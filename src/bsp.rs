@@ -1,22 +1,60 @@
 // https://gamedevelopment.tutsplus.com/tutorials/how-to-use-bsp-trees-to-generate-game-maps--gamedev-12268
-use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
 use rand::Rng;
+use rand_pcg::Pcg64;
+use serde_derive::Serialize;
 use serde_json::from_str;
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
 
 use crate::level::Level;
+use crate::record::{Area, Step};
 use crate::room::Room;
 use crate::tile::Tile;
 
 type RoomJson = Vec<Vec<Tile>>;
 
+// There's no filesystem in the browser, and a game engine calling the C API usually runs
+// somewhere without a `rooms` directory, so the rooms are built in for those, which keeps
+// levels the same as the command line's. Keep this in step with `rooms`, in the same
+// order as the file names.
+const BUILT_IN_ROOMS: [&str; 2] = [
+    include_str!("../rooms/diamond.json"),
+    include_str!("../rooms/obstacles.json"),
+];
+
+fn built_in_rooms() -> std::io::Result<Vec<RoomJson>> {
+    BUILT_IN_ROOMS
+        .iter()
+        .map(|room| Ok(from_str(room)?))
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
 fn load_rooms() -> std::io::Result<Vec<RoomJson>> {
-    let dir = "rooms";
+    built_in_rooms()
+}
+
+// reads `rooms` from the working directory if there is one, so layouts can be added
+// without rebuilding
+#[cfg(not(target_arch = "wasm32"))]
+fn load_rooms() -> std::io::Result<Vec<RoomJson>> {
+    let dir = Path::new("rooms");
+    if !dir.is_dir() {
+        return built_in_rooms();
+    }
+
     let mut rooms = vec![];
 
-    for room in fs::read_dir(dir)? {
-        let room = room?;
-        let path = room.path();
+    // directory order varies between platforms, so sort to keep levels the same
+    let mut paths = fs::read_dir(dir)?
+        .map(|room| room.map(|room| room.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
         let dynamic = fs::read_to_string(path)?;
         let json: RoomJson = from_str(&dynamic)?;
         rooms.push(json);
@@ -25,6 +63,55 @@ fn load_rooms() -> std::io::Result<Vec<RoomJson>> {
     Ok(rooms)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Split {
+    // cut across, into a top and bottom half
+    Horizontal,
+    // cut down, into a left and right half
+    Vertical,
+}
+
+// The area covered by a leaf of the BSP tree, and how it was split. Leaves with no
+// children are the ones rooms are placed in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Partition {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub depth: usize,
+    pub split: Option<Split>,
+    pub children: Vec<Partition>,
+}
+
+impl Partition {
+    // where the area was split, as a line from one point to another
+    pub fn line(&self) -> Option<((i32, i32), (i32, i32))> {
+        let first = self.children.first()?;
+        match self.split? {
+            Split::Horizontal => Some((
+                (self.x, first.y + first.height),
+                (self.x + self.width, first.y + first.height),
+            )),
+            Split::Vertical => Some((
+                (first.x + first.width, self.y),
+                (first.x + first.width, self.y + self.height),
+            )),
+        }
+    }
+
+    // this partition and all the ones below it, parents first
+    pub fn iter(&self) -> Vec<&Partition> {
+        let mut partitions = vec![self];
+        for child in &self.children {
+            partitions.extend(child.iter());
+        }
+
+        partitions
+    }
+}
+
 pub struct BspLevel {
     level: Level,
 }
@@ -34,14 +121,17 @@ impl BspLevel {
         width: i32,
         height: i32,
         hash: &str,
-        // rng: &mut StdRng,
-        rng: &mut StdRng,
+        rng: &mut Pcg64,
         add_walls: bool,
         min_room_width: i32,
         min_room_height: i32,
     ) -> Level {
         let level = Level::new(width, height, hash, min_room_width, min_room_height);
+        BspLevel::generate(level, rng, add_walls)
+    }
 
+    // fills in an empty level, eg one which is recording its steps
+    pub fn generate(level: Level, rng: &mut Pcg64, add_walls: bool) -> Level {
         let mut map = BspLevel { level };
 
         map.place_rooms(rng);
@@ -53,7 +143,7 @@ impl BspLevel {
         map.level
     }
 
-    fn place_rooms(&mut self, rng: &mut StdRng) {
+    fn place_rooms(&mut self, rng: &mut Pcg64) {
         // let prebuilt = vec![
         //     vec![Tile::Walkable, Tile::Walkable, Tile::Walkable, Tile::Walkable, Tile::Walkable, Tile::Walkable],
         //     vec![Tile::Empty, Tile::Empty, Tile::Empty, Tile::Empty, Tile::Walkable, Tile::Walkable],
@@ -94,7 +184,7 @@ impl BspLevel {
         let rooms = match load_rooms() {
             Ok(rooms) => rooms,
             Err(e) => {
-                println!("Error reading room files: {}", e);
+                eprintln!("Error reading room files: {}", e);
                 vec![]
             }
         };
@@ -110,7 +200,11 @@ impl BspLevel {
             self.level.min_room_height,
         );
         root.generate(rng);
-        root.create_rooms(rng, &mut rooms.iter());
+        root.record_splits(&mut self.level);
+        if self.level.keep_tree {
+            self.level.tree = Some(root.partition(0));
+        }
+        root.create_rooms(rng, &mut rooms.iter().collect());
 
         for leaf in root.iter() {
             if leaf.is_leaf() {
@@ -118,9 +212,12 @@ impl BspLevel {
                     self.level.add_room(&room);
                 }
             }
+        }
 
+        // after all the rooms, so prebuilt layouts don't cut corridors off
+        for leaf in root.iter() {
             for corridor in &leaf.corridors {
-                self.level.add_room(corridor);
+                self.level.add_corridor(corridor);
             }
         }
     }
@@ -172,7 +269,7 @@ impl Leaf {
         }
     }
 
-    fn generate(&mut self, rng: &mut StdRng) {
+    fn generate(&mut self, rng: &mut Pcg64) {
         if self.is_leaf() && self.split(rng) {
             if let Some(ref mut left) = self.left_child {
                 left.as_mut().generate(rng);
@@ -184,7 +281,52 @@ impl Leaf {
         }
     }
 
-    fn split(&mut self, rng: &mut StdRng) -> bool {
+    fn partition(&self, depth: usize) -> Partition {
+        let children: Vec<Partition> = [&self.left_child, &self.right_child]
+            .iter()
+            .filter_map(|child| child.as_ref())
+            .map(|child| child.partition(depth + 1))
+            .collect();
+        let split = self
+            .left_child
+            .as_ref()
+            .map(|left| match left.height < self.height {
+                true => Split::Horizontal,
+                false => Split::Vertical,
+            });
+
+        Partition {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            depth,
+            split,
+            children,
+        }
+    }
+
+    // adds a step for each split, in the order they were made
+    fn record_splits(&self, level: &mut Level) {
+        if let (Some(left), Some(right)) = (&self.left_child, &self.right_child) {
+            let horizontal = left.height < self.height;
+            level.record(Step::LeafSplit {
+                area: Area {
+                    x: self.x,
+                    y: self.y,
+                    width: self.width,
+                    height: self.height,
+                },
+                horizontal,
+                position: if horizontal { left.height } else { left.width },
+            });
+
+            left.record_splits(level);
+            right.record_splits(level);
+        }
+    }
+
+    fn split(&mut self, rng: &mut Pcg64) -> bool {
         // if width >25% height, split vertically
         // if height >25% width, split horz
         // otherwise random
@@ -254,10 +396,8 @@ impl Leaf {
         true
     }
 
-    fn create_rooms<'a, I>(&mut self, rng: &mut StdRng, rooms: &mut I)
-    where
-        I: Iterator<Item = &'a Vec<Vec<Tile>>>,
-    {
+    // uses the first prebuilt room which fits in each leaf, if any
+    fn create_rooms(&mut self, rng: &mut Pcg64, rooms: &mut Vec<&RoomJson>) {
         if let Some(ref mut room) = self.left_child {
             room.as_mut().create_rooms(rng, rooms);
         };
@@ -268,14 +408,19 @@ impl Leaf {
 
         // if last level, add a room
         if self.is_leaf() {
-            let room = rooms.next();
+            let room = rooms
+                .iter()
+                .position(|prebuilt| {
+                    prebuilt[0].len() as i32 <= self.width && prebuilt.len() as i32 <= self.height
+                })
+                .map(|index| rooms.remove(index));
             // can't use range with same num, eg 8..8
-            let width = if self.min_room_width == self.width {
+            let width = if self.min_room_width >= self.width {
                 self.width
             } else {
                 rng.gen_range(self.min_room_width..self.width)
             };
-            let height = if self.min_room_height == self.height {
+            let height = if self.min_room_height >= self.height {
                 self.height
             } else {
                 rng.gen_range(self.min_room_height..self.height)
@@ -293,11 +438,14 @@ impl Leaf {
 
             match room {
                 Some(prebuilt) => {
+                    let prebuilt_width = prebuilt[0].len() as i32;
+                    let prebuilt_height = prebuilt.len() as i32;
+                    // keep inside the leaf
                     self.room = Some(Room::new(
-                        x + self.x,
-                        y + self.y,
-                        prebuilt[0].len() as i32,
-                        prebuilt.len() as i32,
+                        x.min(self.width - prebuilt_width) + self.x,
+                        y.min(self.height - prebuilt_height) + self.y,
+                        prebuilt_width,
+                        prebuilt_height,
                         Some(prebuilt.clone()),
                     ))
                 }
@@ -336,23 +484,17 @@ impl Leaf {
         }
     }
 
-    fn iter(&self) -> LeafIterator {
+    fn iter(&self) -> LeafIterator<'_> {
         LeafIterator::new(self)
     }
 }
 
 // corridors are just very narrow rooms
-fn create_corridors(rng: &mut StdRng, left: &mut Box<Leaf>, right: &mut Box<Leaf>) {
+fn create_corridors(rng: &mut Pcg64, left: &mut Box<Leaf>, right: &mut Box<Leaf>) {
     if let (Some(left_room), Some(right_room)) = (left.get_room(), right.get_room()) {
         // pick point in each room
-        let left_point = (
-            rng.gen_range(left_room.x..left_room.x + left_room.width),
-            rng.gen_range(left_room.y..left_room.y + left_room.height),
-        );
-        let right_point = (
-            rng.gen_range(right_room.x..right_room.x + right_room.width),
-            rng.gen_range(right_room.y..right_room.y + right_room.height),
-        );
+        let left_point = pick_point(rng, &left_room);
+        let right_point = pick_point(rng, &right_room);
 
         match rng.gen_range(0..2) {
             0 => {
@@ -411,6 +553,29 @@ fn create_corridors(rng: &mut StdRng, left: &mut Box<Leaf>, right: &mut Box<Leaf
     };
 }
 
+// a random point in a room, moved onto the floor if it lands in a gap in a prebuilt layout
+fn pick_point(rng: &mut Pcg64, room: &Room) -> (i32, i32) {
+    let point = (
+        rng.gen_range(room.x..room.x + room.width),
+        rng.gen_range(room.y..room.y + room.height),
+    );
+
+    if room.layout[(point.1 - room.y) as usize][(point.0 - room.x) as usize].is_walkable() {
+        return point;
+    }
+
+    let mut floor = vec![];
+    for (row, line) in room.layout.iter().enumerate() {
+        for (col, tile) in line.iter().enumerate() {
+            if tile.is_walkable() {
+                floor.push((room.x + col as i32, room.y + row as i32));
+            }
+        }
+    }
+
+    *floor.choose(rng).unwrap_or(&point)
+}
+
 fn horz_corridor(start_x: i32, start_y: i32, end_x: i32) -> Room {
     Room::new(start_x, start_y, (end_x - start_x) + 1, 1, None)
 }
@@ -464,3 +629,62 @@ impl<'a> Iterator for LeafIterator<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::bsp::{built_in_rooms, load_rooms};
+    use crate::builder::DungeonBuilder;
+    use crate::dungeon::Algorithm;
+    use crate::seed::Seed;
+
+    #[test]
+    fn test_tree() {
+        let level = DungeonBuilder::new()
+            .algorithm(Algorithm::Bsp)
+            .seed("tree")
+            .tree(true)
+            .build()
+            .unwrap();
+        let tree = level.tree.as_ref().unwrap();
+
+        assert_eq!((tree.x, tree.y, tree.width, tree.height), (0, 0, 48, 40));
+        assert_eq!(tree.depth, 0);
+        for partition in tree.iter() {
+            assert_eq!(partition.split.is_some(), partition.children.len() == 2);
+            assert!(partition.line().is_some() == partition.split.is_some());
+
+            let area: i32 = partition
+                .children
+                .iter()
+                .map(|child| {
+                    assert_eq!(child.depth, partition.depth + 1);
+                    child.width * child.height
+                })
+                .sum();
+            if !partition.children.is_empty() {
+                assert_eq!(area, partition.width * partition.height);
+            }
+        }
+
+        let leaves = tree.iter().into_iter().filter(|p| p.children.is_empty());
+        assert_eq!(leaves.count(), level.rooms.len());
+    }
+
+    #[test]
+    fn test_tree_not_kept() {
+        let builder = DungeonBuilder::new().algorithm(Algorithm::Bsp).seed("tree");
+
+        assert!(builder.build().unwrap().tree.is_none());
+        let rooms = builder.algorithm(Algorithm::Rooms).tree(true);
+        assert!(rooms.build().unwrap().tree.is_none());
+
+        let mut rng = Seed::new("tree").rng();
+        let level = Algorithm::Bsp.create(48, 40, "tree", &mut rng, false, 4, 5);
+        assert!(level.tree.is_none());
+    }
+
+    #[test]
+    fn test_built_in_rooms() {
+        assert_eq!(built_in_rooms().unwrap(), load_rooms().unwrap());
+    }
+}
@@ -0,0 +1,396 @@
+use std::fmt;
+
+use crate::dungeon::{Algorithm, Dungeon};
+use crate::entity::{populate, PopulationRules};
+use crate::level::Level;
+use crate::locks::add_locks;
+use crate::roles::assign_roles;
+use crate::seed::Seed;
+use crate::tile::Tile;
+
+// the largest width or height a level can have
+pub const MAX_LEVEL_SIZE: i32 = 4096;
+
+// Sets up and generates levels, running the same passes in the same order as the
+// command line tool so a seed gives the same level either way.
+//
+// let level = DungeonBuilder::new()
+//     .size(80, 60)
+//     .algorithm(Algorithm::Bsp)
+//     .seed("hello")
+//     .walls(true)
+//     .roles(true)
+//     .build()?;
+#[derive(Debug, Clone)]
+pub struct DungeonBuilder {
+    width: i32,
+    height: i32,
+    algorithm: Algorithm,
+    seed: Option<Seed>,
+    walls: bool,
+    min_room_width: i32,
+    min_room_height: i32,
+    prune: Option<f32>,
+    roles: bool,
+    locks: usize,
+    population: Option<PopulationRules>,
+    floors: usize,
+    tree: bool,
+}
+
+impl Default for DungeonBuilder {
+    fn default() -> DungeonBuilder {
+        DungeonBuilder {
+            width: 48,
+            height: 40,
+            algorithm: Algorithm::Rooms,
+            seed: None,
+            walls: false,
+            min_room_width: 4,
+            min_room_height: 5,
+            prune: None,
+            roles: false,
+            locks: 0,
+            population: None,
+            floors: 1,
+            tree: false,
+        }
+    }
+}
+
+impl DungeonBuilder {
+    pub fn new() -> DungeonBuilder {
+        DungeonBuilder::default()
+    }
+
+    pub fn size(mut self, width: i32, height: i32) -> DungeonBuilder {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: Algorithm) -> DungeonBuilder {
+        self.algorithm = algorithm;
+        self
+    }
+
+    // a random seed is picked when building if one isn't set
+    pub fn seed<S: Into<Seed>>(mut self, seed: S) -> DungeonBuilder {
+        self.seed = Some(seed.into());
+        self
+    }
+
+    pub fn walls(mut self, walls: bool) -> DungeonBuilder {
+        self.walls = walls;
+        self
+    }
+
+    pub fn min_room_size(mut self, width: i32, height: i32) -> DungeonBuilder {
+        self.min_room_width = width;
+        self.min_room_height = height;
+        self
+    }
+
+    // removes corridor dead ends, keeping this fraction of them
+    pub fn prune(mut self, keep: f32) -> DungeonBuilder {
+        self.prune = Some(keep);
+        self
+    }
+
+    pub fn roles(mut self, roles: bool) -> DungeonBuilder {
+        self.roles = roles;
+        self
+    }
+
+    pub fn locks(mut self, count: usize) -> DungeonBuilder {
+        self.locks = count;
+        self
+    }
+
+    pub fn populate(mut self, rules: PopulationRules) -> DungeonBuilder {
+        self.population = Some(rules);
+        self
+    }
+
+    // keeps the BSP partition tree on each level, for debugging the splits
+    pub fn tree(mut self, tree: bool) -> DungeonBuilder {
+        self.tree = tree;
+        self
+    }
+
+    // only used by build_dungeon
+    pub fn floors(mut self, floors: usize) -> DungeonBuilder {
+        self.floors = floors;
+        self
+    }
+
+    pub fn build(&self) -> Result<Level, BuildError> {
+        let mut dungeon = self.generate(1)?;
+        Ok(dungeon.floors.remove(0))
+    }
+
+    pub fn build_dungeon(&self) -> Result<Dungeon, BuildError> {
+        self.generate(self.floors)
+    }
+
+    // Generates the first floor with a frame kept for each step. Only the generator's
+    // steps are recorded, so pruning, roles, locks and entities aren't applied.
+    pub fn record(&self) -> Result<Level, BuildError> {
+        self.check(1, self.width, self.height)?;
+
+        let seed = self.pick_seed();
+        let mut level = Level::new(
+            self.width,
+            self.height,
+            seed.hash(),
+            self.min_room_width,
+            self.min_room_height,
+        );
+        level.start_recording();
+        if self.tree {
+            level.keep_tree();
+        }
+
+        Ok(self.algorithm.generate(level, &mut seed.rng(), self.walls))
+    }
+
+    // Runs the same passes as build on a board made elsewhere, eg with import::load_board.
+    // The size and algorithm aren't used.
+    pub fn import(&self, board: Vec<Vec<Tile>>) -> Result<Level, BuildError> {
+        let width = board.first().map_or(0, |row| row.len());
+        self.check(1, width as i32, board.len() as i32)?;
+
+        let seed = self.pick_seed();
+        let level = Level::from_board(
+            board,
+            seed.hash(),
+            self.min_room_width,
+            self.min_room_height,
+        );
+        let mut levels = vec![level];
+        self.process(&mut levels, &seed);
+        Ok(levels.remove(0))
+    }
+
+    fn pick_seed(&self) -> Seed {
+        match &self.seed {
+            Some(seed) => seed.clone(),
+            None => Seed::random(),
+        }
+    }
+
+    fn generate(&self, floors: usize) -> Result<Dungeon, BuildError> {
+        self.check(floors, self.width, self.height)?;
+
+        let seed = self.pick_seed();
+        let mut dungeon = Dungeon::create(
+            floors,
+            self.width,
+            self.height,
+            &seed,
+            self.algorithm,
+            self.walls,
+            self.min_room_width,
+            self.min_room_height,
+            self.tree,
+        );
+
+        self.process(&mut dungeon.floors, &seed);
+        Ok(dungeon)
+    }
+
+    // each pass runs over every floor before the next starts
+    fn process(&self, floors: &mut [Level], seed: &Seed) {
+        let mut rng = seed.rng();
+        if let Some(keep) = self.prune {
+            for level in floors.iter_mut() {
+                level.prune_dead_ends(keep, &mut rng);
+            }
+        }
+
+        if self.roles {
+            for level in floors.iter_mut() {
+                assign_roles(level, &mut rng);
+            }
+        }
+
+        if self.locks > 0 {
+            for level in floors.iter_mut() {
+                add_locks(level, self.locks, &mut rng);
+            }
+        }
+
+        if let Some(rules) = &self.population {
+            for level in floors.iter_mut() {
+                populate(level, rules, &mut rng);
+            }
+        }
+    }
+
+    fn check(&self, floors: usize, width: i32, height: i32) -> Result<(), BuildError> {
+        if width < 1 || height < 1 {
+            return Err(BuildError::LevelSize(width, height));
+        }
+
+        if width > MAX_LEVEL_SIZE || height > MAX_LEVEL_SIZE {
+            return Err(BuildError::LevelTooBig(width, height));
+        }
+
+        if self.min_room_width < 1 || self.min_room_height < 1 {
+            return Err(BuildError::RoomSize(
+                self.min_room_width,
+                self.min_room_height,
+            ));
+        }
+
+        if self.min_room_width > width || self.min_room_height > height {
+            return Err(BuildError::RoomTooBig(
+                self.min_room_width,
+                self.min_room_height,
+            ));
+        }
+
+        if let Some(keep) = self.prune {
+            if !(0.0..=1.0).contains(&keep) {
+                return Err(BuildError::Prune(keep));
+            }
+        }
+
+        if floors < 1 {
+            return Err(BuildError::NoFloors);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BuildError {
+    LevelSize(i32, i32),
+    LevelTooBig(i32, i32),
+    RoomSize(i32, i32),
+    RoomTooBig(i32, i32),
+    Prune(f32),
+    NoFloors,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::LevelSize(width, height) => {
+                write!(f, "level size {}x{} is too small", width, height)
+            }
+            BuildError::LevelTooBig(width, height) => write!(
+                f,
+                "level size {}x{} is bigger than {}x{}",
+                width, height, MAX_LEVEL_SIZE, MAX_LEVEL_SIZE
+            ),
+            BuildError::RoomSize(width, height) => {
+                write!(f, "minimum room size {}x{} is too small", width, height)
+            }
+            BuildError::RoomTooBig(width, height) => {
+                write!(
+                    f,
+                    "minimum room size {}x{} doesn't fit in the level",
+                    width, height
+                )
+            }
+            BuildError::Prune(keep) => {
+                write!(f, "prune fraction {} isn't between 0 and 1", keep)
+            }
+            BuildError::NoFloors => write!(f, "a dungeon needs at least one floor"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{BuildError, DungeonBuilder};
+    use crate::dungeon::Algorithm;
+    use crate::seed::Seed;
+
+    #[test]
+    fn test_build_matches_create() {
+        let seed = Seed::new("builder");
+        let level = DungeonBuilder::new()
+            .size(60, 50)
+            .algorithm(Algorithm::Bsp)
+            .seed(seed.clone())
+            .walls(true)
+            .build()
+            .unwrap();
+
+        let mut rng = seed.rng();
+        let created = Algorithm::Bsp.create(60, 50, seed.hash(), &mut rng, true, 4, 5);
+
+        assert_eq!(level.hash, seed.hash());
+        assert_eq!(level.board, created.board);
+    }
+
+    #[test]
+    fn test_build_passes() {
+        let level = DungeonBuilder::new()
+            .seed("passes")
+            .walls(true)
+            .prune(0.0)
+            .roles(true)
+            .locks(1)
+            .build()
+            .unwrap();
+
+        assert!(level.validate().is_ok());
+        assert_eq!(level.locks.len(), 1);
+    }
+
+    #[test]
+    fn test_build_dungeon() {
+        let dungeon = DungeonBuilder::new()
+            .seed("floors")
+            .floors(3)
+            .build_dungeon()
+            .unwrap();
+
+        assert_eq!(dungeon.floors.len(), 3);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let builder = DungeonBuilder::new().seed("errors");
+
+        assert_eq!(
+            builder.clone().size(0, 10).build().err(),
+            Some(BuildError::LevelSize(0, 10))
+        );
+        assert_eq!(
+            builder.clone().min_room_size(4, -1).build().err(),
+            Some(BuildError::RoomSize(4, -1))
+        );
+        assert_eq!(
+            builder.clone().size(5000, 10).build().err(),
+            Some(BuildError::LevelTooBig(5000, 10))
+        );
+        assert_eq!(
+            builder.clone().min_room_size(i32::MAX, 5).build().err(),
+            Some(BuildError::RoomTooBig(i32::MAX, 5))
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .size(10, 10)
+                .min_room_size(4, 11)
+                .build()
+                .err(),
+            Some(BuildError::RoomTooBig(4, 11))
+        );
+        assert_eq!(
+            builder.clone().prune(1.5).build().err(),
+            Some(BuildError::Prune(1.5))
+        );
+        assert_eq!(
+            builder.floors(0).build_dungeon().err(),
+            Some(BuildError::NoFloors)
+        );
+    }
+}
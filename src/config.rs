@@ -0,0 +1,227 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::from_str;
+use std::fs;
+
+use crate::builder::DungeonBuilder;
+use crate::csv::CsvOptions;
+use crate::dungeon::Algorithm;
+use crate::entity::PopulationRules;
+use crate::output::{FormatOptions, OutputFormat};
+use crate::seed::Seed;
+use crate::svg::SvgOptions;
+use crate::text::Glyphs;
+use crate::theme::Theme;
+
+// Every setting the command line tool takes, so a preset can be kept in a file.
+// Settings missing from the file use the defaults below.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // seed or hash, used as with `-s`
+    pub seed: Option<String>,
+    // text to hash for the seed, used as with `-t`
+    pub text: Option<String>,
+    pub algorithm: Algorithm,
+    pub width: i32,
+    pub height: i32,
+    pub walls: bool,
+    pub min_room_width: i32,
+    pub min_room_height: i32,
+    pub floors: usize,
+    // fraction of dead ends to keep, or don't prune if unset
+    pub prune: Option<f32>,
+    pub roles: bool,
+    // keep the BSP partition tree, to write out and draw
+    pub tree: bool,
+    pub locks: usize,
+    // path to spawn rules
+    pub populate: Option<String>,
+    pub format: OutputFormat,
+    // delimiter and tile values for CSV output and import
+    pub csv_options: CsvOptions,
+    // preset name or path to a glyph file, to draw text output with instead of tile numbers
+    pub glyphs: Option<String>,
+    // colour text drawn with glyphs using the theme
+    pub colour: bool,
+    // file to write to instead of stdout
+    pub output: Option<String>,
+    pub draw: bool,
+    // png to draw to, with the floor number added for each floor. Defaults to img/<seed>.png
+    pub image: Option<String>,
+    // svg to draw to, numbered for each floor like image
+    pub svg: Option<String>,
+    pub svg_options: SvgOptions,
+    // preset name or path to a theme file, for png and svg output
+    pub theme: Option<String>,
+    // pixels per tile in images, replacing the theme's
+    pub tile_size: Option<i32>,
+    // gif to record generating the first floor to, or a directory for numbered pngs
+    pub record: Option<String>,
+    // png to read the level from instead of generating one
+    pub import: Option<String>,
+    // colours to read the png with; the theme's colours if unset
+    pub palette: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            seed: None,
+            text: None,
+            algorithm: Algorithm::Rooms,
+            width: 48,
+            height: 40,
+            walls: false,
+            min_room_width: 4,
+            min_room_height: 5,
+            floors: 1,
+            prune: None,
+            roles: false,
+            tree: false,
+            locks: 0,
+            populate: None,
+            format: OutputFormat::Text,
+            csv_options: CsvOptions::default(),
+            glyphs: None,
+            colour: false,
+            output: None,
+            draw: false,
+            image: None,
+            svg: None,
+            svg_options: SvgOptions::default(),
+            theme: None,
+            tile_size: None,
+            record: None,
+            import: None,
+            palette: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> std::io::Result<Config> {
+        let config = fs::read_to_string(path)?;
+        Ok(from_str(&config)?)
+    }
+
+    // None if the seed should be random
+    pub fn seed(&self) -> Option<Seed> {
+        match (&self.seed, &self.text) {
+            (Some(seed), _) => Some(Seed::new(seed)),
+            (None, Some(text)) => Some(Seed::from_text(text)),
+            (None, None) => None,
+        }
+    }
+
+    // loads the theme from a file if it isn't a preset
+    pub fn theme(&self) -> std::io::Result<Theme> {
+        let mut theme = match &self.theme {
+            Some(name) => Theme::find(name)?,
+            None => Theme::default(),
+        };
+        if self.tile_size.is_some() {
+            theme.tile_size = self.tile_size;
+        }
+
+        Ok(theme)
+    }
+
+    // loads the glyphs and theme, if text is drawn with them
+    pub fn format_options(&self) -> std::io::Result<FormatOptions> {
+        let glyphs = match &self.glyphs {
+            Some(name) => Some(Glyphs::find(name)?),
+            None => None,
+        };
+        let colours = match self.colour {
+            true => Some(self.theme()?),
+            false => None,
+        };
+
+        Ok(FormatOptions {
+            csv: self.csv_options.clone(),
+            glyphs,
+            colours,
+        })
+    }
+
+    // loads the spawn rules, if there are any
+    pub fn builder(&self) -> std::io::Result<DungeonBuilder> {
+        let mut builder = DungeonBuilder::new()
+            .size(self.width, self.height)
+            .algorithm(self.algorithm)
+            .walls(self.walls)
+            .min_room_size(self.min_room_width, self.min_room_height)
+            .roles(self.roles)
+            .tree(self.tree)
+            .locks(self.locks)
+            .floors(self.floors);
+
+        if let Some(seed) = self.seed() {
+            builder = builder.seed(seed);
+        }
+
+        if let Some(keep) = self.prune {
+            builder = builder.prune(keep);
+        }
+
+        if let Some(path) = &self.populate {
+            builder = builder.populate(PopulationRules::load(path)?);
+        }
+
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::from_str;
+
+    use crate::builder::DungeonBuilder;
+    use crate::config::Config;
+    use crate::dungeon::Algorithm;
+
+    #[test]
+    fn test_partial_config() {
+        let config: Config =
+            from_str(r#"{ "algorithm": "bsp", "width": 80, "prune": 0.5 }"#).unwrap();
+
+        assert_eq!(config.algorithm, Algorithm::Bsp);
+        assert_eq!(config.width, 80);
+        assert_eq!(config.prune, Some(0.5));
+        assert_eq!(config.height, Config::default().height);
+        assert!(!config.walls);
+    }
+
+    #[test]
+    fn test_load_preset() {
+        let config = Config::load("presets/crypt.json").unwrap();
+
+        assert_eq!(config.floors, 3);
+        assert!(config.builder().is_ok());
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert!(from_str::<Config>(r#"{ "widht": 80 }"#).is_err());
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let config = Config {
+            text: Some("config".to_string()),
+            walls: true,
+            roles: true,
+            ..Config::default()
+        };
+        let level = config.builder().unwrap().build().unwrap();
+        let expected = DungeonBuilder::new()
+            .seed(config.seed().unwrap())
+            .walls(true)
+            .roles(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(level.hash, expected.hash);
+        assert_eq!(level.board, expected.board);
+    }
+}
@@ -0,0 +1,161 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+use crate::tile::{Tile, TILES};
+
+// How boards are written to and read from CSV. By default cells are separated by commas
+// and hold each tile's id, the same numbers as JSON output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    // the value for each tile, in id order: empty, floor, wall, stairs up, stairs down,
+    // door, secret door
+    pub values: Vec<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: ',',
+            values: TILES.iter().map(|tile| tile.id().to_string()).collect(),
+        }
+    }
+}
+
+impl CsvOptions {
+    fn value(&self, tile: Tile) -> String {
+        match self.values.get(tile.id() as usize) {
+            Some(value) => value.clone(),
+            None => tile.id().to_string(),
+        }
+    }
+
+    // blank cells, eg from a spreadsheet, are empty
+    fn tile(&self, value: &str) -> Option<Tile> {
+        if value.is_empty() {
+            return Some(Tile::Empty);
+        }
+
+        match self.values.iter().position(|other| other == value) {
+            Some(index) => TILES.get(index).copied(),
+            None => None,
+        }
+    }
+}
+
+pub fn board_to_csv(board: &[Vec<Tile>], options: &CsvOptions) -> String {
+    let delimiter = options.delimiter.to_string();
+    board
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&tile| options.value(tile))
+                .collect::<Vec<String>>()
+                .join(&delimiter)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// every row has to be the same length; blank lines at the end are skipped
+pub fn board_from_csv(csv: &str, options: &CsvOptions) -> io::Result<Vec<Vec<Tile>>> {
+    let mut board: Vec<Vec<Tile>> = vec![];
+    for (line, text) in csv.trim_end().lines().enumerate() {
+        let row = text
+            .split(options.delimiter)
+            .enumerate()
+            .map(|(column, value)| {
+                options.tile(value.trim()).ok_or_else(|| {
+                    invalid(format!(
+                        "unknown tile {:?} on line {}, column {}",
+                        value,
+                        line + 1,
+                        column + 1
+                    ))
+                })
+            })
+            .collect::<io::Result<Vec<Tile>>>()?;
+
+        if let Some(first) = board.first() {
+            if first.len() != row.len() {
+                return Err(invalid(format!(
+                    "line {} has {} tiles, expected {}",
+                    line + 1,
+                    row.len(),
+                    first.len()
+                )));
+            }
+        }
+        board.push(row);
+    }
+
+    Ok(board)
+}
+
+pub fn load_board(path: &str, options: &CsvOptions) -> io::Result<Vec<Vec<Tile>>> {
+    board_from_csv(&fs::read_to_string(path)?, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DungeonBuilder;
+    use crate::csv::{board_from_csv, board_to_csv, CsvOptions};
+    use crate::level::Level;
+    use crate::tile::Tile;
+
+    #[test]
+    fn test_round_trip() {
+        let level = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("csv")
+            .walls(true)
+            .build()
+            .unwrap();
+        let options = CsvOptions::default();
+        let csv = level.board_to_csv();
+
+        assert_eq!(csv, board_to_csv(&level.board, &options));
+        assert_eq!(board_from_csv(&csv, &options).unwrap(), level.board);
+
+        let read = Level::from_csv(&csv, &options, "csv", 4, 5).unwrap();
+        assert_eq!(read.to_string(), level.to_string());
+        assert_eq!(read.rooms.len(), level.rooms.len());
+    }
+
+    #[test]
+    fn test_options() {
+        let options = CsvOptions {
+            delimiter: ';',
+            values: vec!["-1", "0", "1", "2", "3", "4", "5"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        };
+        let board = vec![
+            vec![Tile::Empty, Tile::Wall, Tile::Wall],
+            vec![Tile::Empty, Tile::Walkable, Tile::Door],
+        ];
+        let csv = board_to_csv(&board, &options);
+
+        assert_eq!(csv, "-1;1;1\n-1;0;4");
+        assert_eq!(board_from_csv(&csv, &options).unwrap(), board);
+        assert_eq!(
+            board_from_csv(";1;1\r\n-1; 0 ;4\n\n", &options).unwrap(),
+            board
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let options = CsvOptions::default();
+
+        assert!(board_from_csv("0,1\n0,1,1", &options).is_err());
+        assert!(board_from_csv("0,1\n0,x", &options).is_err());
+    }
+}
@@ -1,61 +1,235 @@
 use crate::level::Level;
-use crate::tile::Tile;
+use crate::theme::Theme;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+#[cfg(feature = "draw")]
+use crate::{image::image_size, locks::Lock, room::Room, theme::Colour, tile::Tile};
+#[cfg(feature = "draw")]
 use cairo::{Context, Format, ImageSurface};
-use std::fs::File;
-
-fn draw_tile(context: &Context, x: f64, y: f64, x2: f64, y2: f64, colour: (f64, f64, f64)) {
-    // context.set_source_rgb(0.258, 0.525, 0.956);
-    context.set_source_rgb(colour.0, colour.1, colour.2);
-    context.new_path();
-    context.move_to(x, y);
-    context.line_to(x2, y);
-    context.line_to(x, y2);
-    context.move_to(x2, y2);
-    context.line_to(x2, y);
-    context.line_to(x, y2);
-    context.close_path();
+#[cfg(feature = "draw")]
+use std::io::Write;
+
+// without the `draw` feature levels are drawn a pixel at a time, with no antialiasing
+#[cfg(not(feature = "draw"))]
+pub use crate::image::write_png;
+
+#[cfg(feature = "draw")]
+fn draw_tile(context: &Context, col: usize, row: usize, scale: f64, colour: Colour) {
+    let (r, g, b) = colour.rgb();
+    context.set_source_rgb(r, g, b);
+    context.rectangle(col as f64 * scale, row as f64 * scale, scale, scale);
     context.fill().expect("Failed to fill context");
 }
 
-fn draw_tiles(context: &Context, board: &[Vec<Tile>], scale: f64) {
+#[cfg(feature = "draw")]
+fn draw_background(context: &Context, theme: &Theme) {
+    if let Some(colour) = theme.background {
+        let (r, g, b) = colour.rgb();
+        context.set_source_rgb(r, g, b);
+        context.paint().expect("Failed to paint background");
+    }
+}
+
+#[cfg(feature = "draw")]
+fn draw_tiles(context: &Context, board: &[Vec<Tile>], scale: f64, theme: &Theme) {
     for (row, line) in board.iter().enumerate() {
         for (col, tile) in line.iter().enumerate() {
-            match tile {
-                Tile::Walkable => draw_tile(
-                    context,
-                    col as f64 * scale,
-                    row as f64 * scale,
-                    col as f64 * scale + scale,
-                    row as f64 * scale + scale,
-                    (0.258, 0.525, 0.956),
-                ),
-                Tile::Wall => draw_tile(
-                    context,
-                    col as f64 * scale,
-                    row as f64 * scale,
-                    col as f64 * scale + scale,
-                    row as f64 * scale + scale,
-                    (0.956, 0.525, 0.258),
-                ),
-                _ => (),
+            if let Some(colour) = theme.tile(*tile) {
+                draw_tile(context, col, row, scale, colour);
+            }
+        }
+    }
+}
+
+// floor of rooms with a role is coloured to match it
+#[cfg(feature = "draw")]
+fn draw_rooms(context: &Context, rooms: &[Room], board: &[Vec<Tile>], scale: f64, theme: &Theme) {
+    for room in rooms {
+        let colour = match theme.role(room.role) {
+            Some(colour) => colour,
+            None => continue,
+        };
+
+        for row in room.y..room.y2 {
+            for col in room.x..room.x2 {
+                if board[row as usize][col as usize] == Tile::Walkable {
+                    draw_tile(context, col as usize, row as usize, scale, colour);
+                }
             }
         }
     }
 }
 
-pub fn draw(level: &Level, path: &str, img_name: &str) -> Result<(), ::std::io::Error> {
-    let default_output = format!("{}/{}.png", path, img_name);
-    let surface = ImageSurface::create(
-        Format::ARgb32,
-        level.width * level.tile_size,
-        level.height * level.tile_size,
+#[cfg(feature = "draw")]
+fn draw_keys(context: &Context, locks: &[Lock], scale: f64, theme: &Theme) {
+    let (r, g, b) = theme.key.rgb();
+    context.set_source_rgb(r, g, b);
+    for lock in locks {
+        context.rectangle(
+            lock.key.x as f64 * scale + scale / 4.0,
+            lock.key.y as f64 * scale + scale / 4.0,
+            scale / 2.0,
+            scale / 2.0,
+        );
+    }
+    context.fill().expect("Failed to fill context");
+}
+
+#[cfg(feature = "draw")]
+fn draw_grid(context: &Context, level: &Level, scale: f64, theme: &Theme) {
+    let colour = match theme.grid {
+        Some(colour) => colour,
+        None => return,
+    };
+
+    let (r, g, b) = colour.rgb();
+    context.set_source_rgb(r, g, b);
+    context.set_line_width(theme.grid_width);
+    for col in 0..=level.width {
+        context.move_to(col as f64 * scale, 0.0);
+        context.line_to(col as f64 * scale, level.height as f64 * scale);
+    }
+    for row in 0..=level.height {
+        context.move_to(0.0, row as f64 * scale);
+        context.line_to(level.width as f64 * scale, row as f64 * scale);
+    }
+    context.stroke().expect("Failed to draw grid");
+}
+
+#[cfg(feature = "draw")]
+fn draw_outlines(context: &Context, rooms: &[Room], scale: f64, theme: &Theme) {
+    let colour = match theme.outline {
+        Some(colour) => colour,
+        None => return,
+    };
+
+    let (r, g, b) = colour.rgb();
+    context.set_source_rgb(r, g, b);
+    context.set_line_width(theme.outline_width);
+    for room in rooms {
+        context.rectangle(
+            room.x as f64 * scale,
+            room.y as f64 * scale,
+            room.width as f64 * scale,
+            room.height as f64 * scale,
+        );
+    }
+    context.stroke().expect("Failed to draw room outlines");
+}
+
+// lines where a BSP level was split, thicker for earlier splits
+#[cfg(feature = "draw")]
+fn draw_tree(context: &Context, level: &Level, scale: f64, theme: &Theme) {
+    let tree = match &level.tree {
+        Some(tree) => tree,
+        None => return,
+    };
+
+    let (r, g, b) = theme.tree.rgb();
+    context.set_source_rgb(r, g, b);
+    for partition in tree.iter() {
+        if let Some(((x1, y1), (x2, y2))) = partition.line() {
+            context.set_line_width((4.0 - partition.depth as f64).max(1.0));
+            context.move_to(x1 as f64 * scale, y1 as f64 * scale);
+            context.line_to(x2 as f64 * scale, y2 as f64 * scale);
+            context.stroke().expect("Failed to draw tree");
+        }
+    }
+}
+
+// writes the level as a PNG to any sink, eg a file or an HTTP response
+#[cfg(feature = "draw")]
+pub fn write_png<W: Write>(level: &Level, theme: &Theme, writer: &mut W) -> io::Result<()> {
+    let tile_size = theme.tile_size.unwrap_or(level.tile_size);
+    let (width, height) = image_size(level, tile_size)?;
+    let surface = ImageSurface::create(Format::ARgb32, width as i32, height as i32)
+        .map_err(io::Error::other)?;
+    let ctx = Context::new(&surface).map_err(io::Error::other)?;
+    let scale = tile_size as f64;
+
+    draw_background(&ctx, theme);
+    draw_tiles(&ctx, &level.board, scale, theme);
+    draw_rooms(&ctx, &level.rooms, &level.board, scale, theme);
+    draw_grid(&ctx, level, scale, theme);
+    draw_outlines(&ctx, &level.rooms, scale, theme);
+    draw_tree(&ctx, level, scale, theme);
+    draw_keys(&ctx, &level.locks, scale, theme);
+    surface
+        .write_to_png(writer)
+        .map_err(|err| io::Error::other(err.to_string()))
+}
+
+pub fn png_bytes(level: &Level, theme: &Theme) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    write_png(level, theme, &mut bytes)?;
+    Ok(bytes)
+}
+
+// creates any missing directories in the path
+pub fn save(level: &Level, theme: &Theme, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = File::create(path)?;
+    write_png(level, theme, &mut file)
+}
+
+// saves to {path}/{img_name}.png with the classic theme
+pub fn draw(level: &Level, path: &str, img_name: &str) -> io::Result<()> {
+    save(
+        level,
+        &Theme::default(),
+        &Path::new(path).join(format!("{}.png", img_name)),
     )
-    .unwrap();
-    let ctx = Context::new(&surface).expect("failed to create Cairo surface");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DungeonBuilder;
+    use crate::draw::png_bytes;
+    use crate::theme::Theme;
+
+    #[test]
+    fn test_png_bytes() {
+        let level = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("draw")
+            .build()
+            .unwrap();
+        let bytes = png_bytes(&level, &Theme::default()).unwrap();
 
-    draw_tiles(&ctx, &level.board, level.tile_size as f64);
-    let mut file = File::create(default_output)?;
-    surface.write_to_png(&mut file).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_tile_size() {
+        let level = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("draw")
+            .build()
+            .unwrap();
+        let theme = Theme {
+            tile_size: Some(4),
+            ..Theme::preset("parchment").unwrap()
+        };
+        let bytes = png_bytes(&level, &theme).unwrap();
+
+        // width and height from the IHDR chunk
+        assert_eq!(&bytes[16..24], &[0, 0, 0, 80, 0, 0, 0, 40]);
+    }
 
-    Ok(())
+    #[test]
+    fn test_bad_tile_size() {
+        let level = DungeonBuilder::new().size(20, 10).build().unwrap();
+        for size in [0, -4, i32::MAX] {
+            let theme = Theme {
+                tile_size: Some(size),
+                ..Theme::default()
+            };
+            assert!(png_bytes(&level, &theme).is_err());
+        }
+    }
 }
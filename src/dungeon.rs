@@ -0,0 +1,196 @@
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg64;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::bsp::BspLevel;
+use crate::level::Level;
+use crate::room::Point;
+use crate::roomscorridors::RoomsCorridors;
+use crate::seed::Seed;
+use crate::tile::Tile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Bsp,
+    Rooms,
+}
+
+impl Algorithm {
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(
+        &self,
+        width: i32,
+        height: i32,
+        hash: &str,
+        rng: &mut Pcg64,
+        add_walls: bool,
+        min_room_width: i32,
+        min_room_height: i32,
+    ) -> Level {
+        let level = Level::new(width, height, hash, min_room_width, min_room_height);
+        self.generate(level, rng, add_walls)
+    }
+
+    // fills in an empty level, eg one which is recording its steps
+    pub fn generate(&self, level: Level, rng: &mut Pcg64, add_walls: bool) -> Level {
+        match self {
+            Algorithm::Rooms => RoomsCorridors::generate(level, rng, add_walls),
+            Algorithm::Bsp => BspLevel::generate(level, rng, add_walls),
+        }
+    }
+}
+
+// a stack of levels, linked by stairs
+#[derive(Serialize)]
+pub struct Dungeon {
+    pub hash: String,
+    pub floors: Vec<Level>,
+}
+
+impl Dungeon {
+    // the first floor uses the dungeon seed, so is the same as a single level made with it.
+    // Each other floor gets its own seed derived from the first. BSP floors only keep their
+    // partition tree when `tree` is set.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(
+        floors: usize,
+        width: i32,
+        height: i32,
+        seed: &Seed,
+        algorithm: Algorithm,
+        add_walls: bool,
+        min_room_width: i32,
+        min_room_height: i32,
+        tree: bool,
+    ) -> Dungeon {
+        let mut levels: Vec<Level> = vec![];
+
+        for floor in 0..floors {
+            let floor_seed = match floor {
+                0 => seed.clone(),
+                _ => seed.derive(floor),
+            };
+            let mut rng = floor_seed.rng();
+            let mut level = Level::new(
+                width,
+                height,
+                floor_seed.hash(),
+                min_room_width,
+                min_room_height,
+            );
+            if tree {
+                level.keep_tree();
+            }
+            let mut level = algorithm.generate(level, &mut rng, add_walls);
+
+            if let Some(upper) = levels.last_mut() {
+                link_floors(upper, &mut level, &mut rng, add_walls);
+            }
+
+            levels.push(level);
+        }
+
+        Dungeon {
+            hash: seed.hash().to_string(),
+            floors: levels,
+        }
+    }
+}
+
+// places stairs down on the upper floor directly above stairs up on the lower one
+fn link_floors(upper: &mut Level, lower: &mut Level, rng: &mut Pcg64, add_walls: bool) {
+    let mut shared = vec![];
+    let mut shared_in_rooms = vec![];
+    for y in 0..upper.height {
+        for x in 0..upper.width {
+            if upper.board[y as usize][x as usize] == Tile::Walkable
+                && lower.board[y as usize][x as usize] == Tile::Walkable
+            {
+                shared.push(Point { x, y });
+
+                if upper.room_at(x, y).is_some() && lower.room_at(x, y).is_some() {
+                    shared_in_rooms.push(Point { x, y });
+                }
+            }
+        }
+    }
+
+    let stairs = if let Some(point) = shared_in_rooms.choose(rng) {
+        *point
+    } else if let Some(point) = shared.choose(rng) {
+        *point
+    } else if let Some(room) = lower.rooms.choose(rng) {
+        // nowhere lines up, so dig from the upper floor to a room on the lower one
+        let target = room.centre;
+        if let Some(nearest) = upper.rooms.iter().min_by_key(|room| {
+            (room.centre.x - target.x).abs() + (room.centre.y - target.y).abs()
+        }) {
+            let start = nearest.centre;
+            carve_path(upper, &start, &target);
+        }
+
+        if add_walls {
+            upper.add_walls();
+        }
+
+        target
+    } else {
+        return;
+    };
+
+    upper.board[stairs.y as usize][stairs.x as usize] = Tile::StairsDown;
+    lower.board[stairs.y as usize][stairs.x as usize] = Tile::StairsUp;
+}
+
+// leaves any existing stairs in place
+fn carve_path(level: &mut Level, start: &Point, end: &Point) {
+    let horizontal = (start.x.min(end.x)..=start.x.max(end.x)).map(|x| (x, start.y));
+    let vertical = (start.y.min(end.y)..=start.y.max(end.y)).map(|y| (end.x, y));
+
+    for (x, y) in horizontal.chain(vertical) {
+        let tile = &mut level.board[y as usize][x as usize];
+        if !tile.is_walkable() {
+            *tile = Tile::Walkable;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dungeon::{Algorithm, Dungeon};
+    use crate::seed::Seed;
+    use crate::tile::Tile;
+
+    fn find(board: &[Vec<Tile>], tile: Tile) -> Vec<(usize, usize)> {
+        let mut found = vec![];
+        for (y, row) in board.iter().enumerate() {
+            for (x, t) in row.iter().enumerate() {
+                if *t == tile {
+                    found.push((x, y));
+                }
+            }
+        }
+
+        found
+    }
+
+    #[test]
+    fn test_stairs_line_up() {
+        let seed = Seed::new("stairs");
+        let dungeon = Dungeon::create(4, 48, 40, &seed, Algorithm::Rooms, true, 4, 5, false);
+
+        assert_eq!(dungeon.floors.len(), 4);
+        assert_eq!(dungeon.floors[0].hash, seed.hash());
+        assert_eq!(dungeon.floors[1].hash, seed.derive(1).hash());
+        assert!(find(&dungeon.floors[0].board, Tile::StairsUp).is_empty());
+        assert!(find(&dungeon.floors[3].board, Tile::StairsDown).is_empty());
+
+        for floor in 0..3 {
+            let down = find(&dungeon.floors[floor].board, Tile::StairsDown);
+            let up = find(&dungeon.floors[floor + 1].board, Tile::StairsUp);
+            assert_eq!(down.len(), 1);
+            assert_eq!(down, up);
+        }
+    }
+}
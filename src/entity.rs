@@ -0,0 +1,174 @@
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::from_str;
+use std::fs;
+
+use crate::level::Level;
+use crate::tile::Tile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Monster,
+    Item,
+    Trap,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub room: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnEntry {
+    pub kind: EntityKind,
+    pub name: String,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PopulationRules {
+    // entities per tile of room area
+    pub density: f32,
+    // tiles from the start, counted across and down
+    pub min_distance: i32,
+    pub table: Vec<SpawnEntry>,
+}
+
+impl PopulationRules {
+    pub fn load(path: &str) -> std::io::Result<PopulationRules> {
+        let rules = fs::read_to_string(path)?;
+        Ok(from_str(&rules)?)
+    }
+}
+
+// Places entities in rooms, picking each from the weighted table.
+// Entities only go on floor tiles surrounded by room floor, so never in a corridor or doorway.
+pub fn populate(level: &mut Level, rules: &PopulationRules, rng: &mut Pcg64) {
+    let weights = match WeightedIndex::new(rules.table.iter().map(|entry| entry.weight)) {
+        Ok(weights) => weights,
+        Err(_) => return,
+    };
+    let start = level.start();
+
+    for (index, room) in level.rooms.iter().enumerate() {
+        let mut spaces = vec![];
+        for y in room.y..room.y2 {
+            for x in room.x..room.x2 {
+                let far_enough = match start {
+                    Some(start) => (start.x - x).abs() + (start.y - y).abs() >= rules.min_distance,
+                    None => true,
+                };
+                let taken = level.entities.iter().any(|e| e.x == x && e.y == y)
+                    || level
+                        .locks
+                        .iter()
+                        .any(|lock| lock.key.x == x && lock.key.y == y);
+
+                if far_enough && !taken && is_open(level, index, x, y) {
+                    spaces.push((x, y));
+                }
+            }
+        }
+
+        let expected = (room.width * room.height) as f32 * rules.density;
+        let mut count = expected as usize;
+        if rng.gen::<f32>() < expected.fract() {
+            count += 1;
+        }
+
+        spaces.shuffle(rng);
+        for &(x, y) in spaces.iter().take(count) {
+            let entry = &rules.table[weights.sample(rng)];
+            level.entities.push(Entity {
+                kind: entry.kind,
+                name: entry.name.clone(),
+                x,
+                y,
+                room: index,
+            });
+        }
+    }
+}
+
+// floor with more floor of the same room on every side
+fn is_open(level: &Level, room: usize, x: i32, y: i32) -> bool {
+    if level.board[y as usize][x as usize] != Tile::Walkable {
+        return false;
+    }
+
+    [(0, -1), (1, 0), (0, 1), (-1, 0)].iter().all(|(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        level.room_at(nx, ny) == Some(room) && level.board[ny as usize][nx as usize].is_walkable()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::{populate, EntityKind, PopulationRules, SpawnEntry};
+    use crate::level::fixtures::two_rooms;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    fn rules(density: f32, min_distance: i32) -> PopulationRules {
+        PopulationRules {
+            density,
+            min_distance,
+            table: vec![
+                SpawnEntry {
+                    kind: EntityKind::Monster,
+                    name: "goblin".to_string(),
+                    weight: 3,
+                },
+                SpawnEntry {
+                    kind: EntityKind::Item,
+                    name: "potion".to_string(),
+                    weight: 1,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_populate() {
+        let mut level = two_rooms();
+        let board = level.board.clone();
+        let mut rng = Pcg64::seed_from_u64(1);
+        populate(&mut level, &rules(1.0, 0), &mut rng);
+
+        assert_eq!(level.board, board);
+        // only the interior of each room is used
+        assert_eq!(level.entities.len(), 9 + 16);
+        for entity in &level.entities {
+            assert_eq!(level.room_at(entity.x, entity.y), Some(entity.room));
+            assert!(entity.x != 5 && entity.x != 15);
+        }
+    }
+
+    #[test]
+    fn test_populate_min_distance() {
+        let mut level = two_rooms();
+        let mut rng = Pcg64::seed_from_u64(1);
+        populate(&mut level, &rules(1.0, 10), &mut rng);
+
+        assert!(!level.entities.is_empty());
+        assert!(level.entities.iter().all(|entity| entity.room == 1));
+    }
+
+    #[test]
+    fn test_populate_empty_table() {
+        let mut level = two_rooms();
+        let mut rng = Pcg64::seed_from_u64(1);
+        let mut rules = rules(1.0, 0);
+        rules.table.clear();
+        populate(&mut level, &rules, &mut rng);
+
+        assert!(level.entities.is_empty());
+    }
+}
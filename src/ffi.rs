@@ -0,0 +1,401 @@
+// A C API for embedding the generator in game engines, built into the cdylib.
+// `include/dungeon.h` is generated from this file with `cbindgen --output include/dungeon.h`.
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::config::Config;
+use crate::dungeon::Algorithm;
+use crate::level::Level;
+use crate::roles::RoomRole;
+
+pub const DUNGEON_ALGORITHM_ROOMS: c_int = 0;
+pub const DUNGEON_ALGORITHM_BSP: c_int = 1;
+
+// the same numbers as JSON and CSV output
+pub const DUNGEON_TILE_EMPTY: c_int = 0;
+pub const DUNGEON_TILE_FLOOR: c_int = 1;
+pub const DUNGEON_TILE_WALL: c_int = 2;
+pub const DUNGEON_TILE_STAIRS_UP: c_int = 3;
+pub const DUNGEON_TILE_STAIRS_DOWN: c_int = 4;
+pub const DUNGEON_TILE_DOOR: c_int = 5;
+pub const DUNGEON_TILE_SECRET_DOOR: c_int = 6;
+
+pub const DUNGEON_ROLE_NORMAL: c_int = 0;
+pub const DUNGEON_ROLE_ENTRANCE: c_int = 1;
+pub const DUNGEON_ROLE_BOSS: c_int = 2;
+pub const DUNGEON_ROLE_TREASURE: c_int = 3;
+pub const DUNGEON_ROLE_SECRET: c_int = 4;
+
+/// Settings for a level, starting from the command line's defaults.
+pub struct DungeonParams {
+    config: Config,
+}
+
+/// A generated level.
+pub struct DungeonLevel {
+    level: Level,
+    seed: CString,
+}
+
+/// A room's position and size in tiles, and its role if roles are turned on.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DungeonRoom {
+    pub x: c_int,
+    pub y: c_int,
+    pub width: c_int,
+    pub height: c_int,
+    pub role: c_int,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+fn role_id(role: RoomRole) -> c_int {
+    match role {
+        RoomRole::Normal => DUNGEON_ROLE_NORMAL,
+        RoomRole::Entrance => DUNGEON_ROLE_ENTRANCE,
+        RoomRole::Boss => DUNGEON_ROLE_BOSS,
+        RoomRole::Treasure => DUNGEON_ROLE_TREASURE,
+        RoomRole::Secret => DUNGEON_ROLE_SECRET,
+    }
+}
+
+/// Why the last call to `dungeon_generate` on this thread failed, or null if it succeeded
+/// or none has been made. The string belongs to the library and lasts until the next
+/// call to `dungeon_generate`.
+#[no_mangle]
+pub extern "C" fn dungeon_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| match &*error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// New settings, to be freed with `dungeon_params_free`.
+#[no_mangle]
+pub extern "C" fn dungeon_params_new() -> *mut DungeonParams {
+    Box::into_raw(Box::new(DungeonParams {
+        config: Config::default(),
+    }))
+}
+
+/// # Safety
+/// `params` must come from `dungeon_params_new` and not have been freed, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_params_free(params: *mut DungeonParams) {
+    if !params.is_null() {
+        drop(Box::from_raw(params));
+    }
+}
+
+/// Any text or an existing seed, as with `--seed`. A random seed is used if unset or null.
+///
+/// # Safety
+/// `params` must be valid settings, and `seed` a nul-terminated UTF-8 string or null.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_params_set_seed(params: *mut DungeonParams, seed: *const c_char) {
+    let params = &mut *params;
+    params.config.seed = match seed.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(seed).to_string_lossy().into_owned()),
+    };
+}
+
+/// # Safety
+/// `params` must be valid settings.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_params_set_size(
+    params: *mut DungeonParams,
+    width: c_int,
+    height: c_int,
+) {
+    let params = &mut *params;
+    params.config.width = width;
+    params.config.height = height;
+}
+
+/// # Safety
+/// `params` must be valid settings.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_params_set_min_room_size(
+    params: *mut DungeonParams,
+    width: c_int,
+    height: c_int,
+) {
+    let params = &mut *params;
+    params.config.min_room_width = width;
+    params.config.min_room_height = height;
+}
+
+/// One of the `DUNGEON_ALGORITHM_` constants. Returns false, leaving the setting as it
+/// was, for anything else.
+///
+/// # Safety
+/// `params` must be valid settings.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_params_set_algorithm(
+    params: *mut DungeonParams,
+    algorithm: c_int,
+) -> bool {
+    let params = &mut *params;
+    params.config.algorithm = match algorithm {
+        DUNGEON_ALGORITHM_ROOMS => Algorithm::Rooms,
+        DUNGEON_ALGORITHM_BSP => Algorithm::Bsp,
+        _ => return false,
+    };
+
+    true
+}
+
+/// # Safety
+/// `params` must be valid settings.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_params_set_walls(params: *mut DungeonParams, walls: bool) {
+    (*params).config.walls = walls;
+}
+
+/// # Safety
+/// `params` must be valid settings.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_params_set_roles(params: *mut DungeonParams, roles: bool) {
+    (*params).config.roles = roles;
+}
+
+/// Generates the first floor with the settings, returning null if they're invalid; see
+/// `dungeon_last_error`, which is cleared at the start of each call. Levels over 4096
+/// tiles wide or high, and minimum rooms bigger than the level, are turned down before
+/// anything is generated. The level is freed with `dungeon_level_free`.
+///
+/// # Safety
+/// `params` must be valid settings.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_generate(params: *const DungeonParams) -> *mut DungeonLevel {
+    LAST_ERROR.with(|error| *error.borrow_mut() = None);
+    let config = &(*params).config;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        config
+            .builder()
+            .map_err(|err| err.to_string())?
+            .build()
+            .map_err(|err| err.to_string())
+    }));
+
+    let level = match result {
+        Ok(Ok(level)) => level,
+        Ok(Err(message)) => {
+            set_error(message);
+            return ptr::null_mut();
+        }
+        Err(_) => {
+            set_error("generating the level panicked".to_string());
+            return ptr::null_mut();
+        }
+    };
+
+    let seed = CString::new(level.hash.clone()).unwrap_or_default();
+    Box::into_raw(Box::new(DungeonLevel { level, seed }))
+}
+
+/// # Safety
+/// `level` must come from `dungeon_generate` and not have been freed, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_level_free(level: *mut DungeonLevel) {
+    if !level.is_null() {
+        drop(Box::from_raw(level));
+    }
+}
+
+/// The seed the level was made from, to make it again. Lasts as long as the level.
+///
+/// # Safety
+/// `level` must be a valid level.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_level_seed(level: *const DungeonLevel) -> *const c_char {
+    (*level).seed.as_ptr()
+}
+
+/// # Safety
+/// `level` must be a valid level.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_level_width(level: *const DungeonLevel) -> c_int {
+    (*level).level.width
+}
+
+/// # Safety
+/// `level` must be a valid level.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_level_height(level: *const DungeonLevel) -> c_int {
+    (*level).level.height
+}
+
+/// One of the `DUNGEON_TILE_` constants, or -1 if the point is outside the level.
+///
+/// # Safety
+/// `level` must be a valid level.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_level_tile(
+    level: *const DungeonLevel,
+    x: c_int,
+    y: c_int,
+) -> c_int {
+    let board = &(*level).level.board;
+    if x < 0 || y < 0 {
+        return -1;
+    }
+
+    board
+        .get(y as usize)
+        .and_then(|row| row.get(x as usize))
+        .map_or(-1, |tile| tile.id())
+}
+
+/// # Safety
+/// `level` must be a valid level.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_level_room_count(level: *const DungeonLevel) -> usize {
+    (*level).level.rooms.len()
+}
+
+/// Copies the room at `index` into `room`, returning false if there's no such room.
+///
+/// # Safety
+/// `level` must be a valid level and `room` must point to a `DungeonRoom`.
+#[no_mangle]
+pub unsafe extern "C" fn dungeon_level_room(
+    level: *const DungeonLevel,
+    index: usize,
+    room: *mut DungeonRoom,
+) -> bool {
+    let rooms = &(*level).level.rooms;
+    match rooms.get(index) {
+        Some(found) => {
+            *room = DungeonRoom {
+                x: found.x,
+                y: found.y,
+                width: found.width,
+                height: found.height,
+                role: role_id(found.role),
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+
+    use crate::builder::DungeonBuilder;
+    use crate::dungeon::Algorithm;
+    use crate::ffi::*;
+    use crate::tile::TILES;
+
+    #[test]
+    fn test_generate() {
+        let seed = CString::new("ffi").unwrap();
+        unsafe {
+            let params = dungeon_params_new();
+            dungeon_params_set_seed(params, seed.as_ptr());
+            dungeon_params_set_size(params, 40, 30);
+            assert!(dungeon_params_set_algorithm(params, DUNGEON_ALGORITHM_BSP));
+            assert!(!dungeon_params_set_algorithm(params, 7));
+            dungeon_params_set_walls(params, true);
+            dungeon_params_set_roles(params, true);
+            let level = dungeon_generate(params);
+            dungeon_params_free(params);
+
+            let expected = DungeonBuilder::new()
+                .seed("ffi")
+                .size(40, 30)
+                .algorithm(Algorithm::Bsp)
+                .walls(true)
+                .roles(true)
+                .build()
+                .unwrap();
+            assert_eq!(
+                CStr::from_ptr(dungeon_level_seed(level)).to_str().unwrap(),
+                expected.hash
+            );
+            assert_eq!(dungeon_level_width(level), 40);
+            assert_eq!(dungeon_level_height(level), 30);
+            for (y, row) in expected.board.iter().enumerate() {
+                for (x, tile) in row.iter().enumerate() {
+                    assert_eq!(dungeon_level_tile(level, x as i32, y as i32), tile.id());
+                }
+            }
+            assert_eq!(dungeon_level_tile(level, -1, 0), -1);
+            assert_eq!(dungeon_level_tile(level, 40, 0), -1);
+
+            assert_eq!(dungeon_level_room_count(level), expected.rooms.len());
+            let mut room = DungeonRoom {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+                role: 0,
+            };
+            assert!(dungeon_level_room(level, 0, &mut room));
+            assert_eq!(
+                (room.x, room.width),
+                (expected.rooms[0].x, expected.rooms[0].width)
+            );
+            assert!(!dungeon_level_room(level, expected.rooms.len(), &mut room));
+            dungeon_level_free(level);
+        }
+    }
+
+    #[test]
+    fn test_error() {
+        unsafe {
+            let params = dungeon_params_new();
+            dungeon_params_set_size(params, 0, 10);
+
+            assert!(dungeon_generate(params).is_null());
+            let error = CStr::from_ptr(dungeon_last_error()).to_str().unwrap();
+            assert_eq!(error, "level size 0x10 is too small");
+
+            dungeon_params_set_size(params, 100000, 10);
+            assert!(dungeon_generate(params).is_null());
+            dungeon_params_set_size(params, 48, 40);
+            dungeon_params_set_min_room_size(params, c_int::MAX, 5);
+            assert!(dungeon_generate(params).is_null());
+            let error = CStr::from_ptr(dungeon_last_error()).to_str().unwrap();
+            assert!(error.contains("doesn't fit in the level"));
+
+            // a success clears the last failure
+            dungeon_params_set_min_room_size(params, 4, 5);
+            let level = dungeon_generate(params);
+            assert!(!level.is_null());
+            assert!(dungeon_last_error().is_null());
+            dungeon_level_free(level);
+            dungeon_params_free(params);
+        }
+    }
+
+    #[test]
+    fn test_tile_constants() {
+        let constants = [
+            DUNGEON_TILE_EMPTY,
+            DUNGEON_TILE_FLOOR,
+            DUNGEON_TILE_WALL,
+            DUNGEON_TILE_STAIRS_UP,
+            DUNGEON_TILE_STAIRS_DOWN,
+            DUNGEON_TILE_DOOR,
+            DUNGEON_TILE_SECRET_DOOR,
+        ];
+        let ids: Vec<i32> = TILES.iter().map(|tile| tile.id()).collect();
+
+        assert_eq!(constants.to_vec(), ids);
+    }
+}
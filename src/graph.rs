@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+
+use crate::level::Level;
+use crate::room::Point;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub room: usize,
+    // first corridor tile on the way, or none if the rooms touch
+    pub door: Option<Point>,
+}
+
+// which rooms can be walked between without going through another room
+pub struct RoomGraph {
+    pub links: Vec<Vec<Link>>,
+    // corridor tiles next to each room
+    pub exits: Vec<Vec<Point>>,
+}
+
+impl RoomGraph {
+    pub fn new(level: &Level) -> RoomGraph {
+        let mut links = vec![];
+        let mut exits = vec![];
+        let rooms = level.room_map();
+
+        for index in 0..level.rooms.len() {
+            let (room_links, room_exits) = explore(level, &rooms, index);
+            links.push(room_links);
+            exits.push(room_exits);
+        }
+
+        RoomGraph { links, exits }
+    }
+
+    pub fn degree(&self, room: usize) -> usize {
+        self.links[room].len()
+    }
+
+    // number of rooms passed through to get to each room, if it can be reached
+    pub fn distances(&self, from: usize) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.links.len()];
+        let mut queue = VecDeque::new();
+        distances[from] = Some(0);
+        queue.push_back(from);
+
+        while let Some(room) = queue.pop_front() {
+            let distance = distances[room].map(|d| d + 1);
+            for link in &self.links[room] {
+                if distances[link.room].is_none() {
+                    distances[link.room] = distance;
+                    queue.push_back(link.room);
+                }
+            }
+        }
+
+        distances
+    }
+}
+
+fn walkable(level: &Level, x: i32, y: i32) -> bool {
+    x >= 0
+        && y >= 0
+        && x < level.width
+        && y < level.height
+        && level.board[y as usize][x as usize].is_walkable()
+}
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+// follows corridors out of a room to find the rooms they lead to
+fn explore(level: &Level, rooms: &[Vec<Option<usize>>], index: usize) -> (Vec<Link>, Vec<Point>) {
+    let room = &level.rooms[index];
+    let mut links: Vec<Link> = vec![];
+    let mut exits = vec![];
+    // the exit each corridor tile was reached from
+    let mut seen: Vec<Vec<Option<Point>>> =
+        vec![vec![None; level.width as usize]; level.height as usize];
+    let mut queue = VecDeque::new();
+
+    for y in room.y..room.y2 {
+        for x in room.x..room.x2 {
+            if !walkable(level, x, y) || rooms[y as usize][x as usize] != Some(index) {
+                continue;
+            }
+
+            for (dx, dy) in &DIRECTIONS {
+                let (nx, ny) = (x + dx, y + dy);
+                if !walkable(level, nx, ny) {
+                    continue;
+                }
+
+                match rooms[ny as usize][nx as usize] {
+                    Some(other) => add_link(&mut links, index, other, None),
+                    None => {
+                        if seen[ny as usize][nx as usize].is_none() {
+                            let exit = Point { x: nx, y: ny };
+                            seen[ny as usize][nx as usize] = Some(exit);
+                            exits.push(exit);
+                            queue.push_back(exit);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    while let Some(point) = queue.pop_front() {
+        let exit = seen[point.y as usize][point.x as usize];
+        for (dx, dy) in &DIRECTIONS {
+            let (nx, ny) = (point.x + dx, point.y + dy);
+            if !walkable(level, nx, ny) {
+                continue;
+            }
+
+            match rooms[ny as usize][nx as usize] {
+                Some(other) => add_link(&mut links, index, other, exit),
+                None => {
+                    if seen[ny as usize][nx as usize].is_none() {
+                        seen[ny as usize][nx as usize] = exit;
+                        queue.push_back(Point { x: nx, y: ny });
+                    }
+                }
+            }
+        }
+    }
+
+    (links, exits)
+}
+
+fn add_link(links: &mut Vec<Link>, index: usize, other: usize, door: Option<Point>) {
+    if other != index && !links.iter().any(|link| link.room == other) {
+        links.push(Link { room: other, door });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::RoomGraph;
+    use crate::level::fixtures::four_rooms;
+    use crate::room::Point;
+
+    #[test]
+    fn test_links() {
+        let graph = RoomGraph::new(&four_rooms());
+
+        assert_eq!(graph.degree(0), 1);
+        assert_eq!(graph.degree(1), 3);
+        assert_eq!(graph.degree(2), 1);
+        assert_eq!(graph.degree(3), 1);
+        assert_eq!(graph.links[0][0].room, 1);
+        assert_eq!(graph.links[0][0].door, Some(Point { x: 5, y: 2 }));
+        assert_eq!(graph.exits[3], vec![Point { x: 14, y: 8 }]);
+    }
+
+    #[test]
+    fn test_distances() {
+        let graph = RoomGraph::new(&four_rooms());
+        assert_eq!(graph.distances(0), vec![Some(0), Some(1), Some(2), Some(2)]);
+    }
+}
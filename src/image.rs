@@ -0,0 +1,291 @@
+use png::{
+    AdaptiveFilterType, BitDepth, ColorType, Compression, Decoder, Encoder, Transformations,
+};
+use std::io::{self, Read, Write};
+
+use crate::level::Level;
+use crate::theme::{Colour, Theme};
+use crate::tile::Tile;
+
+// largest width or height in pixels a level is drawn at, which is also cairo's limit
+pub const MAX_IMAGE_SIZE: i32 = 32767;
+
+// An RGBA image in memory, read and written as PNG without any native libraries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    // four bytes a pixel, row by row, not premultiplied by alpha
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    // fully transparent
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            data: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * self.width + x) * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.data[offset..offset + 4]);
+        pixel
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, colour: Colour) {
+        let offset = (y * self.width + x) * 4;
+        self.data[offset..offset + 4].copy_from_slice(&[colour.r, colour.g, colour.b, 255]);
+    }
+
+    // Fills a rectangle, rounding its edges to whole pixels and clipping it to the image.
+    // Anything less than a pixel wide or high still covers one pixel.
+    pub fn fill(&mut self, x: f64, y: f64, width: f64, height: f64, colour: Colour) {
+        let span = |start: f64, length: f64, limit: usize| {
+            let from = start.round().max(0.0) as usize;
+            let to = (start + length).round().max(start.round() + 1.0).max(0.0) as usize;
+            from.min(limit)..to.min(limit)
+        };
+
+        for row in span(y, height, self.height) {
+            for col in span(x, width, self.width) {
+                self.set(col, row, colour);
+            }
+        }
+    }
+
+    // a horizontal or vertical line, centred on the points like a cairo stroke
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), width: f64, colour: Colour) {
+        let (x1, x2) = (from.0.min(to.0), from.0.max(to.0));
+        let (y1, y2) = (from.1.min(to.1), from.1.max(to.1));
+        let half = width / 2.0;
+        if x1 == x2 {
+            self.fill(x1 - half, y1, width, y2 - y1, colour);
+        } else {
+            self.fill(x1, y1 - half, x2 - x1, width, colour);
+        }
+    }
+
+    fn outline(&mut self, x: f64, y: f64, width: f64, height: f64, line: f64, colour: Colour) {
+        let half = line / 2.0;
+        self.line((x - half, y), (x + width + half, y), line, colour);
+        self.line(
+            (x - half, y + height),
+            (x + width + half, y + height),
+            line,
+            colour,
+        );
+        self.line((x, y), (x, y + height), line, colour);
+        self.line((x + width, y), (x + width, y + height), line, colour);
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        // levels are mostly flat colour, which squashes down a lot
+        encoder.set_compression(Compression::Best);
+        encoder.set_adaptive_filter(AdaptiveFilterType::Adaptive);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        Ok(writer.finish()?)
+    }
+
+    // reads any 8 or 16 bit PNG, converting it to RGBA
+    pub fn read_png<R: Read>(reader: R) -> io::Result<Image> {
+        let mut decoder = Decoder::new(reader);
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let pixels = buffer[..info.buffer_size()].chunks(info.line_size);
+
+        let mut image = Image::new(info.width as usize, info.height as usize);
+        let mut data = image.data.chunks_mut(4);
+        for line in pixels {
+            let channels = info.color_type.samples();
+            for source in line[..info.width as usize * channels].chunks(channels) {
+                let pixel = data.next().expect("image is the size of the png");
+                let rgba = match *source {
+                    [grey] => [grey, grey, grey, 255],
+                    [grey, alpha] => [grey, grey, grey, alpha],
+                    [r, g, b] => [r, g, b, 255],
+                    [r, g, b, alpha] => [r, g, b, alpha],
+                    _ => unreachable!("palettes are expanded to colours"),
+                };
+                pixel.copy_from_slice(&rgba);
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+// The width and height in pixels of the level drawn with tiles `tile_size` pixels wide,
+// or an error if the tile size is less than 1 or the image would be too big to make.
+pub fn image_size(level: &Level, tile_size: i32) -> io::Result<(usize, usize)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    if tile_size < 1 {
+        return Err(invalid(format!("tile size {} is less than 1", tile_size)));
+    }
+
+    let pixels = |tiles: i32| {
+        tiles
+            .checked_mul(tile_size)
+            .filter(|&pixels| pixels > 0 && pixels <= MAX_IMAGE_SIZE)
+    };
+    match (pixels(level.width), pixels(level.height)) {
+        (Some(width), Some(height)) => Ok((width as usize, height as usize)),
+        _ => Err(invalid(format!(
+            "a {}x{} level with {} pixel tiles is too big to draw, images can be at most {} pixels across",
+            level.width, level.height, tile_size, MAX_IMAGE_SIZE
+        ))),
+    }
+}
+
+// Draws the level the same way as `draw` with cairo, but a pixel at a time without
+// antialiasing, so lines thinner than a pixel are drawn a pixel wide.
+pub fn draw_level(level: &Level, theme: &Theme) -> io::Result<Image> {
+    let tile_size = theme.tile_size.unwrap_or(level.tile_size);
+    let (image_width, image_height) = image_size(level, tile_size)?;
+    let scale = tile_size as f64;
+    let mut image = Image::new(image_width, image_height);
+    let (width, height) = (level.width as f64 * scale, level.height as f64 * scale);
+
+    if let Some(colour) = theme.background {
+        image.fill(0.0, 0.0, width, height, colour);
+    }
+
+    let rooms = level.room_map();
+    for (row, line) in level.board.iter().enumerate() {
+        for (col, &tile) in line.iter().enumerate() {
+            // floor of rooms with a role is coloured to match it
+            let role = rooms[row][col].and_then(|room| theme.role(level.rooms[room].role));
+            let colour = match (tile, role) {
+                (Tile::Walkable, Some(colour)) => Some(colour),
+                _ => theme.tile(tile),
+            };
+            if let Some(colour) = colour {
+                image.fill(col as f64 * scale, row as f64 * scale, scale, scale, colour);
+            }
+        }
+    }
+
+    if let Some(colour) = theme.grid {
+        for col in 0..=level.width {
+            let x = col as f64 * scale;
+            image.line((x, 0.0), (x, height), theme.grid_width, colour);
+        }
+        for row in 0..=level.height {
+            let y = row as f64 * scale;
+            image.line((0.0, y), (width, y), theme.grid_width, colour);
+        }
+    }
+
+    if let Some(colour) = theme.outline {
+        for room in &level.rooms {
+            image.outline(
+                room.x as f64 * scale,
+                room.y as f64 * scale,
+                room.width as f64 * scale,
+                room.height as f64 * scale,
+                theme.outline_width,
+                colour,
+            );
+        }
+    }
+
+    // thicker for earlier splits
+    if let Some(tree) = &level.tree {
+        for partition in tree.iter() {
+            if let Some(((x1, y1), (x2, y2))) = partition.line() {
+                image.line(
+                    (x1 as f64 * scale, y1 as f64 * scale),
+                    (x2 as f64 * scale, y2 as f64 * scale),
+                    (4.0 - partition.depth as f64).max(1.0),
+                    theme.tree,
+                );
+            }
+        }
+    }
+
+    for lock in &level.locks {
+        image.fill(
+            lock.key.x as f64 * scale + scale / 4.0,
+            lock.key.y as f64 * scale + scale / 4.0,
+            scale / 2.0,
+            scale / 2.0,
+            theme.key,
+        );
+    }
+
+    Ok(image)
+}
+
+// writes the level as a PNG to any sink without needing cairo
+pub fn write_png<W: Write>(level: &Level, theme: &Theme, writer: &mut W) -> io::Result<()> {
+    draw_level(level, theme)?.write_png(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DungeonBuilder;
+    use crate::image::{draw_level, image_size, Image};
+    use crate::theme::{Colour, Theme};
+
+    #[test]
+    fn test_round_trip() {
+        let mut image = Image::new(3, 2);
+        image.set(0, 0, Colour::new(1, 2, 3));
+        image.fill(1.0, 1.0, 5.0, 5.0, Colour::new(255, 0, 0));
+        let mut bytes = vec![];
+        image.write_png(&mut bytes).unwrap();
+        let read = Image::read_png(bytes.as_slice()).unwrap();
+
+        assert_eq!(read, image);
+        assert_eq!(read.pixel(0, 0), [1, 2, 3, 255]);
+        assert_eq!(read.pixel(0, 1), [0, 0, 0, 0]);
+        assert_eq!(read.pixel(2, 1), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_draw_level() {
+        let level = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("image")
+            .walls(true)
+            .build()
+            .unwrap();
+        let theme = Theme {
+            tile_size: Some(4),
+            ..Theme::preset("dark").unwrap()
+        };
+        let image = draw_level(&level, &theme).unwrap();
+
+        assert_eq!((image.width, image.height), (80, 40));
+        let room = &level.rooms[0];
+        let (x, y) = (room.x as usize * 4 + 2, room.y as usize * 4 + 2);
+        let floor = theme.floor;
+        assert_eq!(image.pixel(x, y), [floor.r, floor.g, floor.b, 255]);
+    }
+
+    #[test]
+    fn test_image_size() {
+        let level = DungeonBuilder::new().size(20, 10).build().unwrap();
+
+        assert_eq!(image_size(&level, 4).unwrap(), (80, 40));
+        assert!(image_size(&level, 0).is_err());
+        assert!(image_size(&level, -4).is_err());
+        assert!(image_size(&level, 10000).is_err());
+        assert!(image_size(&level, i32::MAX).is_err());
+
+        let theme = Theme {
+            tile_size: Some(-4),
+            ..Theme::default()
+        };
+        assert!(draw_level(&level, &theme).is_err());
+    }
+}
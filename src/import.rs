@@ -0,0 +1,235 @@
+use serde_derive::Deserialize;
+use serde_json::from_str;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+
+use crate::image::Image;
+use crate::theme::{Colour, Theme};
+use crate::tile::Tile;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaletteFile {
+    #[serde(default = "one")]
+    tile_size: i32,
+    tiles: HashMap<String, Tile>,
+}
+
+fn one() -> i32 {
+    1
+}
+
+// Which tile each colour in an image stands for, eg
+// { "tile_size": 1, "tiles": { "#ffffff": 0, "#000000": 2, "#4286f4": 1 } }
+// Fully transparent pixels are always empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    // pixels per tile; each tile is read from the pixel in its centre
+    pub tile_size: i32,
+    pub tiles: Vec<(Colour, Tile)>,
+}
+
+impl Palette {
+    pub fn load(path: &str) -> io::Result<Palette> {
+        let file: PaletteFile = from_str(&fs::read_to_string(path)?)?;
+        let mut tiles = vec![];
+        for (hex, tile) in file.tiles {
+            let colour = Colour::from_hex(&hex).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid colour {}, expected #rrggbb", hex),
+                )
+            })?;
+            tiles.push((colour, tile));
+        }
+
+        Palette::new(file.tile_size, tiles)
+    }
+
+    // reads back images drawn with the theme, as long as they have no keys or role colours
+    pub fn from_theme(theme: &Theme, tile_size: i32) -> io::Result<Palette> {
+        let tiles = [
+            Tile::Walkable,
+            Tile::Wall,
+            Tile::StairsUp,
+            Tile::StairsDown,
+            Tile::Door,
+            Tile::SecretDoor,
+        ];
+        let mut tiles: Vec<(Colour, Tile)> = tiles
+            .iter()
+            .filter_map(|&tile| theme.tile(tile).map(|colour| (colour, tile)))
+            .collect();
+        if let Some(colour) = theme.background {
+            tiles.push((colour, Tile::Empty));
+        }
+
+        Palette::new(tile_size, tiles)
+    }
+
+    // a colour can only stand for one tile, so "#FFFFFF" and "#ffffff" can't both be given
+    fn new(tile_size: i32, tiles: Vec<(Colour, Tile)>) -> io::Result<Palette> {
+        for (i, (colour, _)) in tiles.iter().enumerate() {
+            if tiles[..i].iter().any(|(other, _)| other == colour) {
+                return Err(invalid(format!(
+                    "colour {} is used for more than one tile",
+                    colour.hex()
+                )));
+            }
+        }
+
+        Ok(Palette { tile_size, tiles })
+    }
+
+    fn tile(&self, colour: Colour) -> Option<Tile> {
+        self.tiles
+            .iter()
+            .find(|(other, _)| *other == colour)
+            .map(|&(_, tile)| tile)
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// reads a board from a PNG, to pass to Level::from_board
+pub fn board_from_png<R: Read>(reader: &mut R, palette: &Palette) -> io::Result<Vec<Vec<Tile>>> {
+    let image = Image::read_png(reader).map_err(|err| invalid(err.to_string()))?;
+    let size = palette.tile_size;
+    if size < 1 {
+        return Err(invalid(format!("tile size {} is too small", size)));
+    }
+    let size = size as usize;
+    if image.width % size != 0 || image.height % size != 0 {
+        return Err(invalid(format!(
+            "image size {}x{} isn't a multiple of the tile size {}",
+            image.width, image.height, size
+        )));
+    }
+
+    let (width, height) = (image.width / size, image.height / size);
+    let mut board = vec![vec![Tile::Empty; width]; height];
+    for (row, line) in board.iter_mut().enumerate() {
+        for (col, tile) in line.iter_mut().enumerate() {
+            let x = col * size + size / 2;
+            let y = row * size + size / 2;
+            let [r, g, b, alpha] = image.pixel(x, y);
+            if alpha == 0 {
+                continue;
+            }
+
+            let colour = Colour::new(r, g, b);
+            *tile = palette.tile(colour).ok_or_else(|| {
+                invalid(format!(
+                    "colour {} at pixel {},{} isn't in the palette",
+                    colour.hex(),
+                    x,
+                    y
+                ))
+            })?;
+        }
+    }
+
+    Ok(board)
+}
+
+pub fn load_board(path: &str, palette: &Palette) -> io::Result<Vec<Vec<Tile>>> {
+    board_from_png(&mut File::open(path)?, palette)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DungeonBuilder;
+    use crate::draw::png_bytes;
+    use crate::import::{board_from_png, Palette};
+    use crate::level::Level;
+    use crate::theme::{Colour, Theme, PRESETS};
+    use crate::tile::Tile;
+
+    #[test]
+    fn test_round_trip() {
+        let level = DungeonBuilder::new()
+            .size(30, 20)
+            .seed("import")
+            .walls(true)
+            .build()
+            .unwrap();
+        let theme = Theme::preset("dark").unwrap();
+        let bytes = png_bytes(&level, &theme).unwrap();
+        let palette = Palette::from_theme(&theme, level.tile_size).unwrap();
+        let board = board_from_png(&mut bytes.as_slice(), &palette).unwrap();
+
+        assert_eq!(board, level.board);
+
+        let imported = Level::from_board(board, "import", 4, 5);
+        assert_eq!(imported.rooms.len(), level.rooms.len());
+        assert!(imported.walls);
+        assert!(imported.validate().is_ok());
+    }
+
+    #[test]
+    fn test_unknown_colour() {
+        let level = DungeonBuilder::new()
+            .size(10, 10)
+            .seed("import")
+            .build()
+            .unwrap();
+        let bytes = png_bytes(&level, &Theme::default()).unwrap();
+        let palette = Palette {
+            tile_size: 16,
+            tiles: vec![(Colour::new(0, 0, 0), Tile::Wall)],
+        };
+
+        assert!(board_from_png(&mut bytes.as_slice(), &palette).is_err());
+    }
+
+    #[test]
+    fn test_palette_file() {
+        let palette = Palette::load("palettes/sketch.json").unwrap();
+
+        assert_eq!(palette.tile_size, 1);
+        assert_eq!(palette.tile(Colour::new(0, 0, 0)), Some(Tile::Wall));
+        assert!(Palette::load("palettes/none.json").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_colours() {
+        let path =
+            std::env::temp_dir().join(format!("dungeon-palette-{}.json", std::process::id()));
+        let json = r##"{ "tiles": { "#FFFFFF": 0, "#ffffff": 1 } }"##;
+        std::fs::write(&path, json).unwrap();
+        let loaded = Palette::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+
+        let theme = Theme {
+            stairs_down: Theme::default().stairs_up,
+            ..Theme::default()
+        };
+        assert!(Palette::from_theme(&theme, 16).is_err());
+    }
+
+    #[test]
+    fn test_presets_round_trip() {
+        let dungeon = DungeonBuilder::new()
+            .size(30, 20)
+            .seed("presets")
+            .walls(true)
+            .floors(2)
+            .build_dungeon()
+            .unwrap();
+
+        for name in PRESETS.iter() {
+            let theme = Theme::preset(name).unwrap();
+            for level in dungeon.floors.iter() {
+                let bytes = png_bytes(level, &theme).unwrap();
+                let palette = Palette::from_theme(&theme, level.tile_size).unwrap();
+                let board = board_from_png(&mut bytes.as_slice(), &palette).unwrap();
+
+                assert_eq!(board, level.board, "{}", name);
+            }
+        }
+    }
+}
@@ -1,9 +1,21 @@
+use rand::Rng;
+use rand_pcg::Pcg64;
 use serde_derive::Serialize;
+use std::collections::VecDeque;
 use std::fmt;
 
-use crate::room::Room;
+use crate::bsp::Partition;
+use crate::csv::{board_from_csv, board_to_csv, CsvOptions};
+use crate::entity::Entity;
+use crate::locks::Lock;
+use crate::record::{Area, Frame, Step};
+use crate::roles::RoomRole;
+use crate::room::{Point, Room};
 use crate::tile::Tile;
 
+// pixels per tile when drawing, unless a theme says otherwise
+pub const TILE_SIZE: i32 = 16;
+
 #[derive(Serialize)]
 pub struct Level {
     pub hash: String,
@@ -12,8 +24,20 @@ pub struct Level {
     pub height: i32,
     pub board: Vec<Vec<Tile>>,
     pub rooms: Vec<Room>,
+    pub corridors: Vec<Room>,
+    pub entities: Vec<Entity>,
+    pub locks: Vec<Lock>,
+    pub walls: bool,
     pub min_room_width: i32,
     pub min_room_height: i32,
+    // how a BSP level was split up, if asked to keep it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<Partition>,
+    #[serde(skip)]
+    pub(crate) keep_tree: bool,
+    // snapshots of each generation step, only kept when recording
+    #[serde(skip)]
+    pub frames: Option<Vec<Frame>>,
 }
 
 impl Level {
@@ -31,18 +55,151 @@ impl Level {
         }
 
         Level {
-            tile_size: 16,
+            tile_size: TILE_SIZE,
             width,
             height,
             board,
             rooms: vec![],
+            corridors: vec![],
+            entities: vec![],
+            locks: vec![],
+            walls: false,
             hash: hash.to_string(),
             min_room_width,
             min_room_height,
+            tree: None,
+            keep_tree: false,
+            frames: None,
+        }
+    }
+
+    // A level made elsewhere, eg drawn in a paint program. Rectangles of floor at least the
+    // minimum room size become rooms, and the rest of the floor is split into corridors
+    // one tile wide, so the post-processors can work on it like a generated level.
+    pub fn from_board(
+        board: Vec<Vec<Tile>>,
+        hash: &str,
+        min_room_width: i32,
+        min_room_height: i32,
+    ) -> Self {
+        let height = board.len() as i32;
+        let width = board.first().map_or(0, |row| row.len()) as i32;
+        let mut level = Level::new(width, height, hash, min_room_width, min_room_height);
+        level.walls = board.iter().flatten().any(|&tile| tile == Tile::Wall);
+        level.board = board;
+
+        // doors lead into rooms rather than being part of them
+        let floor =
+            |tile: Tile| tile.is_walkable() && tile != Tile::Door && tile != Tile::SecretDoor;
+        let mut used = vec![vec![false; width as usize]; height as usize];
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                if used[y][x] || !floor(level.board[y][x]) {
+                    continue;
+                }
+
+                let free = |x: usize, y: usize| !used[y][x] && floor(level.board[y][x]);
+                let right = (x..width as usize).take_while(|&x| free(x, y)).count();
+                let down = (y..height as usize)
+                    .take_while(|&y| (x..x + right).all(|x| free(x, y)))
+                    .count();
+                if (right as i32) < min_room_width || (down as i32) < min_room_height {
+                    continue;
+                }
+
+                let layout = level.board[y..y + down]
+                    .iter()
+                    .map(|row| row[x..x + right].to_vec())
+                    .collect();
+                for row in used.iter_mut().skip(y).take(down) {
+                    for tile in row.iter_mut().skip(x).take(right) {
+                        *tile = true;
+                    }
+                }
+                level.rooms.push(Room::new(
+                    x as i32,
+                    y as i32,
+                    right as i32,
+                    down as i32,
+                    Some(layout),
+                ));
+            }
+        }
+
+        // runs across first, then whatever's left going down
+        for across in [true, false] {
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    let free = |x: usize, y: usize| !used[y][x] && level.board[y][x].is_walkable();
+                    if !free(x, y) {
+                        continue;
+                    }
+
+                    let length = match across {
+                        true => (x..width as usize).take_while(|&x| free(x, y)).count(),
+                        false => (y..height as usize).take_while(|&y| free(x, y)).count(),
+                    };
+                    if across && length < 2 {
+                        continue;
+                    }
+
+                    let (run_width, run_height) = match across {
+                        true => (length, 1),
+                        false => (1, length),
+                    };
+                    for row in used.iter_mut().skip(y).take(run_height) {
+                        for tile in row.iter_mut().skip(x).take(run_width) {
+                            *tile = true;
+                        }
+                    }
+                    level.corridors.push(Room::new(
+                        x as i32,
+                        y as i32,
+                        run_width as i32,
+                        run_height as i32,
+                        None,
+                    ));
+                }
+            }
+        }
+
+        level
+    }
+
+    // asks the BSP generator to keep its partition tree in `tree`
+    pub fn keep_tree(&mut self) {
+        self.keep_tree = true;
+    }
+
+    // keeps a copy of the board with each step from now on
+    pub fn start_recording(&mut self) {
+        self.frames = Some(vec![]);
+    }
+
+    pub fn record(&mut self, step: Step) {
+        if let Some(frames) = &mut self.frames {
+            frames.push(Frame {
+                step,
+                board: self.board.clone(),
+            });
         }
     }
 
     pub fn add_room(&mut self, room: &Room) {
+        self.carve(room);
+        self.record(Step::RoomPlaced(Area::of(room)));
+        self.rooms.push(room.clone());
+    }
+
+    // corridors are carved like rooms but kept separate,
+    // so they can be told apart when post-processing
+    pub fn add_corridor(&mut self, corridor: &Room) {
+        self.carve(corridor);
+        self.record(Step::CorridorCarved(Area::of(corridor)));
+        self.corridors.push(corridor.clone());
+    }
+
+    fn carve(&mut self, room: &Room) {
         for row in 0..room.layout.len() {
             for col in 0..room.layout[row].len() {
                 let y = room.y as usize + row;
@@ -51,15 +208,21 @@ impl Level {
                 self.board[y][x] = room.layout[row][col];
             }
         }
+    }
 
-        self.rooms.push(room.clone());
+    // index of the room containing the tile, if any
+    pub fn room_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.rooms
+            .iter()
+            .position(|room| x >= room.x && x < room.x2 && y >= room.y && y < room.y2)
     }
 
     pub fn add_walls(&mut self) {
+        self.walls = true;
         // TODO add corners
         for y in 0..self.board.len() {
             for x in 0..self.board[y].len() {
-                if self.board[y][x] == Tile::Walkable {
+                if self.board[y][x].is_walkable() {
                     // ugly code to avoid overflow (ie < 0 in usize)
                     if x >= 1 {
                         if y >= 1 {
@@ -82,6 +245,8 @@ impl Level {
                 }
             }
         }
+
+        self.record(Step::WallsAdded);
     }
 
     fn add_wall(&mut self, x: usize, y: usize) {
@@ -94,36 +259,465 @@ impl Level {
         }
     }
 
-    pub fn board_to_csv(&self) -> String {
-        let mut output = Vec::new();
-        for row in 0..self.height as usize {
-            let mut row_output = Vec::new();
-            for col in 0..self.width as usize {
-                if self.board[row][col] == Tile::Empty {
-                    row_output.push("0".to_string());
-                } else {
-                    row_output.push(format!("{}", self.board[row][col]));
+    // where the player arrives: the stairs up if there are any,
+    // otherwise the entrance or first room
+    pub fn start(&self) -> Option<Point> {
+        for (y, row) in self.board.iter().enumerate() {
+            if let Some(x) = row.iter().position(|&tile| tile == Tile::StairsUp) {
+                return Some(Point {
+                    x: x as i32,
+                    y: y as i32,
+                });
+            }
+        }
+
+        self.rooms
+            .iter()
+            .find(|room| room.role == RoomRole::Entrance)
+            .or_else(|| self.rooms.first())
+            .map(|room| room.centre)
+    }
+
+    // where the level is left from: the stairs down if there are any,
+    // otherwise the boss or last room
+    pub fn exit(&self) -> Option<Point> {
+        for (y, row) in self.board.iter().enumerate() {
+            if let Some(x) = row.iter().position(|&tile| tile == Tile::StairsDown) {
+                return Some(Point {
+                    x: x as i32,
+                    y: y as i32,
+                });
+            }
+        }
+
+        self.rooms
+            .iter()
+            .find(|room| room.role == RoomRole::Boss)
+            .or_else(|| self.rooms.last())
+            .map(|room| room.centre)
+    }
+
+    // room index for every tile, for when room_at would be called a lot
+    pub fn room_map(&self) -> Vec<Vec<Option<usize>>> {
+        let mut map = vec![vec![None; self.width as usize]; self.height as usize];
+        for (index, room) in self.rooms.iter().enumerate().rev() {
+            for y in room.y.max(0)..room.y2.min(self.height) {
+                for x in room.x.max(0)..room.x2.min(self.width) {
+                    map[y as usize][x as usize] = Some(index);
+                }
+            }
+        }
+
+        map
+    }
+
+    // Removes corridor stubs: walkable tiles outside rooms with only one walkable neighbour.
+    // Runs until no stubs are left, except for a `keep` fraction (0.0 - 1.0) of the
+    // original dead ends, which are left in place along with the corridor leading to them.
+    // Walls are regenerated afterwards if the level has them.
+    pub fn prune_dead_ends(&mut self, keep: f32, rng: &mut Pcg64) {
+        let rooms = self.room_map();
+        let kept: Vec<(usize, usize)> = self
+            .dead_ends(&rooms)
+            .into_iter()
+            .filter(|_| rng.gen::<f32>() < keep)
+            .collect();
+
+        let mut removed = vec![];
+        loop {
+            let dead_ends: Vec<(usize, usize)> = self
+                .dead_ends(&rooms)
+                .into_iter()
+                .filter(|tile| !kept.contains(tile))
+                .collect();
+
+            if dead_ends.is_empty() {
+                break;
+            }
+
+            for &(x, y) in &dead_ends {
+                self.board[y][x] = Tile::Empty;
+            }
+            removed.extend(dead_ends);
+        }
+
+        if self.walls && !removed.is_empty() {
+            self.remove_walls(&rooms);
+            self.add_walls();
+        }
+    }
+
+    fn dead_ends(&self, rooms: &[Vec<Option<usize>>]) -> Vec<(usize, usize)> {
+        let mut dead_ends = vec![];
+        for (y, row) in rooms.iter().enumerate() {
+            for (x, room) in row.iter().enumerate() {
+                if self.board[y][x] == Tile::Walkable
+                    && room.is_none()
+                    && self.count_walkable_around(x, y) == 1
+                {
+                    dead_ends.push((x, y));
+                }
+            }
+        }
+
+        dead_ends
+    }
+
+    // clears walls outside rooms, leaving any that are part of a room layout
+    fn remove_walls(&mut self, rooms: &[Vec<Option<usize>>]) {
+        for (row, room_row) in self.board.iter_mut().zip(rooms) {
+            for (tile, room) in row.iter_mut().zip(room_row) {
+                if *tile == Tile::Wall && room.is_none() {
+                    *tile = Tile::Empty;
+                }
+            }
+        }
+    }
+
+    fn count_walkable_around(&self, x: usize, y: usize) -> usize {
+        self.neighbours(x, y, false)
+            .iter()
+            .filter(|&&(nx, ny)| self.board[ny][nx].is_walkable())
+            .count()
+    }
+
+    // number of neighbouring tiles of a type, optionally including diagonals
+    fn count_around(&self, x: usize, y: usize, tile: Tile, diagonals: bool) -> usize {
+        self.neighbours(x, y, diagonals)
+            .iter()
+            .filter(|&&(nx, ny)| self.board[ny][nx] == tile)
+            .count()
+    }
+
+    // positions of neighbouring tiles which are on the board
+    fn neighbours(&self, x: usize, y: usize, diagonals: bool) -> Vec<(usize, usize)> {
+        let mut neighbours = vec![];
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx == 0 && dy == 0) || (!diagonals && dx != 0 && dy != 0) {
+                    continue;
+                }
+
+                let nx = x as i32 + dx;
+                let ny = y as i32 + dy;
+                if nx < 0 || ny < 0 || nx >= self.width || ny >= self.height {
+                    continue;
+                }
+
+                neighbours.push((nx as usize, ny as usize));
+            }
+        }
+
+        neighbours
+    }
+
+    // Checks the level is usable: every room is on the board and doesn't overlap another,
+    // all the floor can be walked between, and if walls were added, no floor is left
+    // open to empty space.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        for (index, room) in self.rooms.iter().enumerate() {
+            if room.x < 0 || room.y < 0 || room.x2 > self.width || room.y2 > self.height {
+                return Err(ValidationError::RoomOutOfBounds(index));
+            }
+
+            for (other_index, other) in self.rooms.iter().enumerate().skip(index + 1) {
+                if room.x < other.x2 && room.x2 > other.x && room.y < other.y2 && room.y2 > other.y
+                {
+                    return Err(ValidationError::RoomsOverlap(index, other_index));
+                }
+            }
+        }
+
+        let floor: Vec<(usize, usize)> = (0..self.height as usize)
+            .flat_map(|y| (0..self.width as usize).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.board[y][x].is_walkable())
+            .collect();
+
+        if let Some(&(x, y)) = floor.first() {
+            let mut reached = vec![vec![false; self.width as usize]; self.height as usize];
+            let mut queue = VecDeque::new();
+            reached[y][x] = true;
+            queue.push_back((x, y));
+
+            while let Some((x, y)) = queue.pop_front() {
+                for (nx, ny) in self.neighbours(x, y, false) {
+                    if self.board[ny][nx].is_walkable() && !reached[ny][nx] {
+                        reached[ny][nx] = true;
+                        queue.push_back((nx, ny));
+                    }
                 }
             }
 
-            output.push(row_output.join(","));
+            if let Some(&(x, y)) = floor.iter().find(|&&(x, y)| !reached[y][x]) {
+                return Err(ValidationError::Disconnected(Point {
+                    x: x as i32,
+                    y: y as i32,
+                }));
+            }
         }
 
-        output.join("\n")
+        if self.walls {
+            if let Some(&(x, y)) = floor
+                .iter()
+                .find(|&&(x, y)| self.count_around(x, y, Tile::Empty, true) > 0)
+            {
+                return Err(ValidationError::OpenFloor(Point {
+                    x: x as i32,
+                    y: y as i32,
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> LevelStats {
+        let floor_tiles = self
+            .board
+            .iter()
+            .flatten()
+            .filter(|tile| tile.is_walkable())
+            .count();
+
+        LevelStats {
+            rooms: self.rooms.len(),
+            corridors: self.corridors.len(),
+            floor_tiles,
+            dead_ends: self.dead_ends(&self.room_map()).len(),
+            entities: self.entities.len(),
+            locks: self.locks.len(),
+        }
+    }
+
+    pub fn board_to_csv(&self) -> String {
+        self.to_csv(&CsvOptions::default())
+    }
+
+    pub fn to_csv(&self, options: &CsvOptions) -> String {
+        board_to_csv(&self.board, options)
+    }
+
+    // reads a board written by to_csv or a spreadsheet, finding rooms as from_board does
+    pub fn from_csv(
+        csv: &str,
+        options: &CsvOptions,
+        hash: &str,
+        min_room_width: i32,
+        min_room_height: i32,
+    ) -> std::io::Result<Self> {
+        let board = board_from_csv(csv, options)?;
+        Ok(Level::from_board(
+            board,
+            hash,
+            min_room_width,
+            min_room_height,
+        ))
     }
 }
 
-impl fmt::Display for Level {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.hash)?;
+// counts for comparing levels, eg in a batch manifest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LevelStats {
+    pub rooms: usize,
+    pub corridors: usize,
+    pub floor_tiles: usize,
+    pub dead_ends: usize,
+    pub entities: usize,
+    pub locks: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    RoomOutOfBounds(usize),
+    RoomsOverlap(usize, usize),
+    Disconnected(Point),
+    OpenFloor(Point),
+}
 
-        for row in 0..self.height as usize {
-            for col in 0..self.width as usize {
-                write!(f, "{} ", self.board[row][col])?
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::RoomOutOfBounds(room) => write!(f, "Room {} is off the board", room),
+            ValidationError::RoomsOverlap(room, other) => {
+                write!(f, "Rooms {} and {} overlap", room, other)
+            }
+            ValidationError::Disconnected(point) => {
+                write!(f, "Floor at {}, {} can't be reached", point.x, point.y)
+            }
+            ValidationError::OpenFloor(point) => {
+                write!(f, "Floor at {}, {} isn't walled in", point.x, point.y)
             }
-            writeln!(f)?
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.board {
+            let tiles: Vec<String> = row.iter().map(|tile| tile.to_string()).collect();
+            writeln!(f, "{}", tiles.join(" "))?
         }
 
         Ok(())
     }
 }
+
+// small levels built by hand for tests across the crate
+#[cfg(test)]
+pub mod fixtures {
+    use crate::level::Level;
+    use crate::room::Room;
+    use crate::tile::Tile;
+
+    // a room in the corner with a corridor running off to nowhere
+    pub fn stub_level() -> Level {
+        let mut level = Level::new(12, 6, "test", 3, 3);
+        level.add_room(&Room::new(1, 1, 3, 3, None));
+        for x in 4..9 {
+            level.board[2][x] = Tile::Walkable;
+        }
+
+        level
+    }
+
+    // two rooms joined by a corridor
+    pub fn two_rooms() -> Level {
+        let mut level = Level::new(24, 8, "test", 4, 4);
+        level.add_room(&Room::new(1, 1, 5, 5, None));
+        level.add_room(&Room::new(15, 1, 6, 6, None));
+        for x in 6..15 {
+            level.board[3][x] = Tile::Walkable;
+        }
+
+        level
+    }
+
+    // three rooms in a row, and one below the middle
+    pub fn four_rooms() -> Level {
+        let mut level = Level::new(40, 14, "test", 4, 4);
+        level.add_room(&Room::new(1, 1, 4, 4, None));
+        level.add_room(&Room::new(12, 1, 5, 5, None));
+        level.add_room(&Room::new(24, 1, 4, 4, None));
+        level.add_room(&Room::new(12, 9, 4, 4, None));
+        for x in 5..12 {
+            level.board[2][x] = Tile::Walkable;
+        }
+        for x in 17..24 {
+            level.board[3][x] = Tile::Walkable;
+        }
+        for y in 6..9 {
+            level.board[y][14] = Tile::Walkable;
+        }
+
+        level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::fixtures;
+    use crate::level::{Level, ValidationError};
+    use crate::room::{Point, Room};
+    use crate::tile::Tile;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    #[test]
+    fn test_room_at() {
+        let level = fixtures::stub_level();
+        assert_eq!(level.room_at(1, 1), Some(0));
+        assert_eq!(level.room_at(3, 3), Some(0));
+        assert_eq!(level.room_at(4, 2), None);
+        assert_eq!(level.room_at(0, 0), None);
+    }
+
+    #[test]
+    fn test_from_board() {
+        let level = Level::from_board(fixtures::stub_level().board, "test", 3, 3);
+
+        assert_eq!(level.rooms.len(), 1);
+        assert_eq!((level.rooms[0].x, level.rooms[0].y), (1, 1));
+        assert_eq!(level.corridors.len(), 1);
+        assert_eq!(level.corridors[0].width, 5);
+        assert!(!level.walls);
+    }
+
+    #[test]
+    fn test_prune_dead_ends() {
+        let mut level = fixtures::stub_level();
+        let mut rng = Pcg64::seed_from_u64(1);
+        level.prune_dead_ends(0.0, &mut rng);
+
+        let mut expected = Level::new(12, 6, "test", 3, 3);
+        expected.add_room(&Room::new(1, 1, 3, 3, None));
+        assert_eq!(level.board, expected.board);
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = fixtures::stub_level().stats();
+
+        assert_eq!(stats.rooms, 1);
+        assert_eq!(stats.floor_tiles, 14);
+        assert_eq!(stats.dead_ends, 1);
+        assert_eq!(stats.locks, 0);
+    }
+
+    #[test]
+    fn test_prune_keeps_dead_ends() {
+        let mut level = fixtures::stub_level();
+        let mut rng = Pcg64::seed_from_u64(1);
+        level.prune_dead_ends(1.0, &mut rng);
+
+        assert_eq!(level.board, fixtures::stub_level().board);
+    }
+
+    #[test]
+    fn test_prune_regenerates_walls() {
+        let mut level = fixtures::stub_level();
+        level.add_walls();
+        let mut rng = Pcg64::seed_from_u64(1);
+        level.prune_dead_ends(0.0, &mut rng);
+
+        let mut expected = Level::new(12, 6, "test", 3, 3);
+        expected.add_room(&Room::new(1, 1, 3, 3, None));
+        expected.add_walls();
+        assert_eq!(level.board, expected.board);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut level = fixtures::stub_level();
+        assert_eq!(level.validate(), Ok(()));
+
+        level.add_walls();
+        assert_eq!(level.validate(), Ok(()));
+
+        level.board[4][10] = Tile::Walkable;
+        assert_eq!(
+            level.validate(),
+            Err(ValidationError::Disconnected(Point { x: 10, y: 4 }))
+        );
+    }
+
+    #[test]
+    fn test_validate_walls() {
+        let mut level = fixtures::stub_level();
+        level.add_walls();
+        level.board[1][5] = Tile::Empty;
+        assert_eq!(
+            level.validate(),
+            Err(ValidationError::OpenFloor(Point { x: 4, y: 2 }))
+        );
+    }
+
+    #[test]
+    fn test_validate_rooms() {
+        let mut level = fixtures::stub_level();
+        level.rooms.push(Room::new(3, 3, 3, 3, None));
+        assert_eq!(level.validate(), Err(ValidationError::RoomsOverlap(0, 1)));
+
+        level.rooms[1] = Room::new(10, 4, 3, 3, None);
+        assert_eq!(level.validate(), Err(ValidationError::RoomOutOfBounds(1)));
+    }
+}
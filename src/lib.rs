@@ -1,7 +1,32 @@
 pub mod draw;
 pub mod tile;
 pub mod level;
+pub mod locks;
 #[macro_use]
 pub mod room;
+pub mod roles;
 pub mod roomscorridors;
+pub mod batch;
 pub mod bsp;
+pub mod builder;
+pub mod config;
+pub mod csv;
+pub mod dungeon;
+pub mod entity;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
+pub mod graph;
+pub mod image;
+pub mod import;
+pub mod output;
+pub mod record;
+pub mod seed;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod svg;
+pub mod text;
+pub mod theme;
+#[cfg(feature = "view")]
+pub mod view;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
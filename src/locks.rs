@@ -0,0 +1,250 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde_derive::Serialize;
+use std::collections::VecDeque;
+
+use crate::level::Level;
+use crate::roles::RoomRole;
+use crate::room::Point;
+use crate::tile::Tile;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Lock {
+    pub id: usize,
+    pub door: Point,
+    pub key: Point,
+}
+
+// Adds up to `count` locked doors, each with a key which can be picked up without
+// going through that door. Doors go in corridors at the entrance to a room, and only
+// where they'd shut off part of the level. Returns how many were added.
+pub fn add_locks(level: &mut Level, count: usize, rng: &mut Pcg64) -> usize {
+    let start = match level.start() {
+        Some(start) => start,
+        None => return 0,
+    };
+    let existing = level.locks.len();
+    let rooms = level.room_map();
+    let mut doors = candidate_doors(level, &rooms);
+    doors.shuffle(rng);
+
+    for door in doors {
+        if level.locks.len() - existing >= count {
+            break;
+        }
+
+        let next_to_door = [(0, -1), (1, 0), (0, 1), (-1, 0)].iter().any(|(dx, dy)| {
+            let (x, y) = (door.x + dx, door.y + dy);
+            x >= 0
+                && y >= 0
+                && x < level.width
+                && y < level.height
+                && level.board[y as usize][x as usize] == Tile::Door
+        });
+        let before = reachable(level, &start);
+        if next_to_door || !before[door.y as usize][door.x as usize] {
+            continue;
+        }
+
+        // a door without a lock can't be opened yet
+        level.board[door.y as usize][door.x as usize] = Tile::Door;
+        let after = reachable(level, &start);
+
+        let shuts_off_room = (0..level.height).any(|y| {
+            (0..level.width).any(|x| {
+                before[y as usize][x as usize]
+                    && !after[y as usize][x as usize]
+                    && rooms[y as usize][x as usize].is_some()
+            })
+        });
+
+        let mut spaces = vec![];
+        for y in 0..level.height {
+            for x in 0..level.width {
+                let point = Point { x, y };
+                if after[y as usize][x as usize]
+                    && point != start
+                    && level.board[y as usize][x as usize] == Tile::Walkable
+                    && rooms[y as usize][x as usize]
+                        .is_some_and(|room| level.rooms[room].role != RoomRole::Secret)
+                    && !level.locks.iter().any(|lock| lock.key == point)
+                    && !level.entities.iter().any(|e| e.x == x && e.y == y)
+                {
+                    spaces.push(point);
+                }
+            }
+        }
+
+        match (shuts_off_room, spaces.choose(rng)) {
+            (true, Some(&key)) => {
+                level.locks.push(Lock {
+                    id: level.locks.len(),
+                    door,
+                    key,
+                });
+
+                if !is_solvable(level) {
+                    level.locks.pop();
+                    level.board[door.y as usize][door.x as usize] = Tile::Walkable;
+                }
+            }
+            _ => level.board[door.y as usize][door.x as usize] = Tile::Walkable,
+        }
+    }
+
+    level.locks.len() - existing
+}
+
+// every key can be collected, and every door opened, starting from the start
+pub fn is_solvable(level: &Level) -> bool {
+    let start = match level.start() {
+        Some(start) => start,
+        None => return level.locks.is_empty(),
+    };
+    let reached = reachable(level, &start);
+
+    level.locks.iter().all(|lock| {
+        reached[lock.key.y as usize][lock.key.x as usize]
+            && reached[lock.door.y as usize][lock.door.x as usize]
+    })
+}
+
+// Tiles which can be walked to, picking up keys on the way.
+// Searches again whenever a new key is found, until no more are.
+fn reachable(level: &Level, start: &Point) -> Vec<Vec<bool>> {
+    let mut held: Vec<usize> = vec![];
+
+    loop {
+        let mut reached = vec![vec![false; level.width as usize]; level.height as usize];
+        let mut queue = VecDeque::new();
+        reached[start.y as usize][start.x as usize] = true;
+        queue.push_back(*start);
+
+        while let Some(point) = queue.pop_front() {
+            for (dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                let (x, y) = (point.x + dx, point.y + dy);
+                if x < 0 || y < 0 || x >= level.width || y >= level.height {
+                    continue;
+                }
+
+                let tile = level.board[y as usize][x as usize];
+                let passable = match tile {
+                    Tile::Door => level
+                        .locks
+                        .iter()
+                        .any(|lock| lock.door == Point { x, y } && held.contains(&lock.id)),
+                    _ => tile.is_walkable(),
+                };
+
+                if passable && !reached[y as usize][x as usize] {
+                    reached[y as usize][x as usize] = true;
+                    queue.push_back(Point { x, y });
+                }
+            }
+        }
+
+        let found: Vec<usize> = level
+            .locks
+            .iter()
+            .filter(|lock| {
+                !held.contains(&lock.id) && reached[lock.key.y as usize][lock.key.x as usize]
+            })
+            .map(|lock| lock.id)
+            .collect();
+
+        if found.is_empty() {
+            return reached;
+        }
+
+        held.extend(found);
+    }
+}
+
+// single-width corridor tiles next to a room
+fn candidate_doors(level: &Level, rooms: &[Vec<Option<usize>>]) -> Vec<Point> {
+    let walkable = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && x < level.width
+            && y < level.height
+            && level.board[y as usize][x as usize].is_walkable()
+    };
+    let in_room = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && x < level.width
+            && y < level.height
+            && rooms[y as usize][x as usize].is_some()
+    };
+
+    let mut doors = vec![];
+    for y in 0..level.height {
+        for x in 0..level.width {
+            if level.board[y as usize][x as usize] != Tile::Walkable || in_room(x, y) {
+                continue;
+            }
+
+            let across = walkable(x - 1, y)
+                && walkable(x + 1, y)
+                && !walkable(x, y - 1)
+                && !walkable(x, y + 1);
+            let down = walkable(x, y - 1)
+                && walkable(x, y + 1)
+                && !walkable(x - 1, y)
+                && !walkable(x + 1, y);
+            let by_room =
+                in_room(x - 1, y) || in_room(x + 1, y) || in_room(x, y - 1) || in_room(x, y + 1);
+
+            if (across || down) && by_room {
+                doors.push(Point { x, y });
+            }
+        }
+    }
+
+    doors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::fixtures::two_rooms;
+    use crate::locks::{add_locks, is_solvable, Lock};
+    use crate::room::Point;
+    use crate::tile::Tile;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    #[test]
+    fn test_add_locks() {
+        let mut level = two_rooms();
+        let mut rng = Pcg64::seed_from_u64(1);
+
+        // one door at either end of the corridor
+        assert_eq!(add_locks(&mut level, 3, &mut rng), 2);
+        assert!(is_solvable(&level));
+
+        for lock in &level.locks {
+            assert_eq!(
+                level.board[lock.door.y as usize][lock.door.x as usize],
+                Tile::Door
+            );
+            assert!(lock.door == Point { x: 6, y: 3 } || lock.door == Point { x: 14, y: 3 });
+            assert_eq!(level.room_at(lock.key.x, lock.key.y), Some(0));
+        }
+    }
+
+    #[test]
+    fn test_key_behind_door() {
+        let mut level = two_rooms();
+        level.board[3][10] = Tile::Door;
+        level.locks.push(Lock {
+            id: 0,
+            door: Point { x: 10, y: 3 },
+            key: Point { x: 17, y: 3 },
+        });
+
+        assert!(!is_solvable(&level));
+
+        level.locks[0].key = Point { x: 2, y: 2 };
+        assert!(is_solvable(&level));
+    }
+}
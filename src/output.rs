@@ -0,0 +1,135 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::csv::CsvOptions;
+use crate::dungeon::Dungeon;
+use crate::level::Level;
+use crate::text::{to_text, Glyphs};
+use crate::theme::Theme;
+
+// How a level is written out. Each gives a single document ending in a newline, so
+// output can be piped straight into other tools.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    // the board as printed by Display, or drawn with glyphs if they're set
+    Text,
+    Json,
+    Csv,
+}
+
+// settings for the formats which have any
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    pub csv: CsvOptions,
+    pub glyphs: Option<Glyphs>,
+    // colours text drawn with glyphs using ANSI escape codes
+    pub colours: Option<Theme>,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        }
+    }
+
+    pub fn level(&self, level: &Level, options: &FormatOptions) -> serde_json::Result<String> {
+        match self {
+            OutputFormat::Text => match &options.glyphs {
+                Some(glyphs) => Ok(to_text(level, glyphs, options.colours.as_ref())),
+                None => Ok(level.to_string()),
+            },
+            OutputFormat::Json => Ok(format!("{}\n", serde_json::to_string(level)?)),
+            OutputFormat::Csv => Ok(format!("{}\n", level.to_csv(&options.csv))),
+        }
+    }
+
+    // a dungeon with one floor is written the same as a level. Otherwise JSON lists the
+    // floors, and text and CSV boards are separated by a blank line.
+    pub fn dungeon(
+        &self,
+        dungeon: &Dungeon,
+        options: &FormatOptions,
+    ) -> serde_json::Result<String> {
+        match (self, dungeon.floors.as_slice()) {
+            (_, [level]) => self.level(level, options),
+            (OutputFormat::Json, _) => Ok(format!("{}\n", serde_json::to_string(dungeon)?)),
+            _ => {
+                let floors = dungeon
+                    .floors
+                    .iter()
+                    .map(|level| self.level(level, options))
+                    .collect::<serde_json::Result<Vec<String>>>()?;
+                Ok(floors.join("\n"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DungeonBuilder;
+    use crate::output::{FormatOptions, OutputFormat};
+    use crate::text::Glyphs;
+
+    #[test]
+    fn test_csv_output() {
+        let level = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("output")
+            .build()
+            .unwrap();
+        let output = OutputFormat::Csv
+            .level(&level, &FormatOptions::default())
+            .unwrap();
+
+        assert_eq!(output.lines().count(), 10);
+        assert!(output.lines().all(|line| line.split(',').count() == 20));
+        assert!(output.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_glyph_output() {
+        let level = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("output")
+            .build()
+            .unwrap();
+        let options = FormatOptions {
+            glyphs: Some(Glyphs::default()),
+            ..FormatOptions::default()
+        };
+        let output = OutputFormat::Text.level(&level, &options).unwrap();
+
+        assert_eq!(output.lines().count(), 10);
+        assert!(output.lines().all(|line| line.chars().count() == 20));
+        assert!(output.contains('.'));
+    }
+
+    #[test]
+    fn test_dungeon_output() {
+        let dungeon = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("output")
+            .floors(2)
+            .build_dungeon()
+            .unwrap();
+
+        let text = OutputFormat::Text
+            .dungeon(&dungeon, &FormatOptions::default())
+            .unwrap();
+        assert_eq!(text.lines().count(), 21);
+        assert_eq!(text.lines().nth(10), Some(""));
+
+        let json = OutputFormat::Json
+            .dungeon(&dungeon, &FormatOptions::default())
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["floors"].as_array().map(|floors| floors.len()),
+            Some(2)
+        );
+    }
+}
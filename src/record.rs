@@ -0,0 +1,279 @@
+use gif::{DisposalMethod, Encoder, Repeat};
+use serde_derive::Serialize;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::image::{image_size, Image};
+use crate::level::Level;
+use crate::room::Room;
+use crate::theme::{Colour, Theme};
+use crate::tile::Tile;
+
+// A rectangle of the level, in tiles
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Area {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Area {
+    pub fn of(room: &Room) -> Area {
+        Area {
+            x: room.x,
+            y: room.y,
+            width: room.width,
+            height: room.height,
+        }
+    }
+}
+
+// Something a generator did to the level, in the order it happened
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    // a BSP leaf cut in two, `position` tiles from its top or left edge
+    LeafSplit {
+        #[serde(flatten)]
+        area: Area,
+        horizontal: bool,
+        position: i32,
+    },
+    RoomPlaced(Area),
+    CorridorCarved(Area),
+    WallsAdded,
+}
+
+// the board just after a step
+#[derive(Debug, Clone, Serialize)]
+pub struct Frame {
+    pub step: Step,
+    pub board: Vec<Vec<Tile>>,
+}
+
+// hundredths of a second each frame is shown for; the finished level is held for longer
+const FRAME_DELAY: u16 = 10;
+const LAST_FRAME_DELAY: u16 = 300;
+
+const WHITE: Colour = Colour::new(255, 255, 255);
+const GREY: Colour = Colour::new(204, 204, 204);
+const HIGHLIGHT: Colour = Colour::new(255, 0, 144);
+
+// indexes into the palette after the tiles
+const EMPTY: u8 = 0;
+const SPLIT: u8 = 7;
+const CURRENT: u8 = 8;
+
+// Tiles use the theme's colours. Leaf splits are drawn in its grid colour, and the area
+// changed by each step is outlined in its outline colour.
+fn palette(theme: &Theme) -> [Colour; 9] {
+    [
+        theme.background.unwrap_or(WHITE),
+        theme.floor,
+        theme.wall,
+        theme.stairs_up,
+        theme.stairs_down,
+        theme.door,
+        theme.secret_door,
+        theme.grid.unwrap_or(GREY),
+        theme.outline.unwrap_or(HIGHLIGHT),
+    ]
+}
+
+// tiles come first in the palette, in id order
+fn index(tile: Tile) -> u8 {
+    tile.id() as u8
+}
+
+// A frame as palette indexes, one per pixel. Splits so far stay on screen, so the
+// finished tree can be seen under the rooms.
+fn raster(frames: &[Frame], current: usize, scale: usize) -> Vec<u8> {
+    let board = &frames[current].board;
+    let height = board.len() * scale;
+    let width = board.first().map_or(0, |row| row.len()) * scale;
+    let mut pixels = vec![EMPTY; width * height];
+
+    for (y, pixel_row) in pixels.chunks_mut(width.max(1)).enumerate() {
+        for (x, pixel) in pixel_row.iter_mut().enumerate() {
+            *pixel = index(board[y / scale][x / scale]);
+        }
+    }
+
+    let mut fill = |left: usize, top: usize, right: usize, bottom: usize, colour: u8| {
+        for y in top..bottom.min(height) {
+            for x in left..right.min(width) {
+                pixels[y * width + x] = colour;
+            }
+        }
+    };
+
+    for frame in &frames[..=current] {
+        if let Step::LeafSplit {
+            area,
+            horizontal,
+            position,
+        } = frame.step
+        {
+            let (x, y) = (area.x as usize * scale, area.y as usize * scale);
+            let (x2, y2) = (
+                (area.x + area.width) as usize * scale,
+                (area.y + area.height) as usize * scale,
+            );
+            if horizontal {
+                let split = (area.y + position) as usize * scale;
+                fill(x, split, x2, split + 1, SPLIT);
+            } else {
+                let split = (area.x + position) as usize * scale;
+                fill(split, y, split + 1, y2, SPLIT);
+            }
+        }
+    }
+
+    let area = match frames[current].step {
+        Step::LeafSplit { area, .. } | Step::RoomPlaced(area) | Step::CorridorCarved(area) => area,
+        Step::WallsAdded => return pixels,
+    };
+    let line = (scale / 8).max(1);
+    let (x, y) = (area.x as usize * scale, area.y as usize * scale);
+    let (x2, y2) = (
+        (area.x + area.width) as usize * scale,
+        (area.y + area.height) as usize * scale,
+    );
+    fill(x, y, x2, y + line, CURRENT);
+    fill(x, y2.saturating_sub(line), x2, y2, CURRENT);
+    fill(x, y, x + line, y2, CURRENT);
+    fill(x2.saturating_sub(line), y, x2, y2, CURRENT);
+
+    pixels
+}
+
+fn frames(level: &Level) -> &[Frame] {
+    level.frames.as_deref().unwrap_or(&[])
+}
+
+// plays the level's recorded steps once a tenth of a second, then loops
+pub fn write_gif<W: Write>(level: &Level, theme: &Theme, writer: W) -> io::Result<()> {
+    let tile_size = theme.tile_size.unwrap_or(level.tile_size);
+    let (width, height) = image_size(level, tile_size)?;
+    let scale = tile_size as usize;
+    let too_big = || io::Error::other("level is too big for a gif");
+    let width = u16::try_from(width).map_err(|_| too_big())?;
+    let height = u16::try_from(height).map_err(|_| too_big())?;
+
+    let colours: Vec<u8> = palette(theme)
+        .iter()
+        .flat_map(|colour| [colour.r, colour.g, colour.b])
+        .collect();
+    let mut encoder = Encoder::new(writer, width, height, &colours).map_err(io::Error::other)?;
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(io::Error::other)?;
+
+    let frames = frames(level);
+    for current in 0..frames.len() {
+        let frame = gif::Frame {
+            width,
+            height,
+            delay: match current + 1 == frames.len() {
+                true => LAST_FRAME_DELAY,
+                false => FRAME_DELAY,
+            },
+            dispose: DisposalMethod::Background,
+            transparent: theme.background.map_or(Some(EMPTY), |_| None),
+            buffer: Cow::Owned(raster(frames, current, scale)),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+
+    Ok(())
+}
+
+// creates any missing directories in the path
+pub fn save_gif(level: &Level, theme: &Theme, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    write_gif(level, theme, File::create(path)?)
+}
+
+// writes each recorded step to {dir}/0000.png, {dir}/0001.png and so on
+pub fn save_frames(level: &Level, theme: &Theme, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let tile_size = theme.tile_size.unwrap_or(level.tile_size);
+    let (width, height) = image_size(level, tile_size)?;
+    let scale = tile_size as usize;
+    fs::create_dir_all(dir)?;
+    let colours = palette(theme);
+    let frames = frames(level);
+    let mut paths = vec![];
+
+    for current in 0..frames.len() {
+        let mut image = Image::new(width, height);
+        for (i, &pixel) in raster(frames, current, scale).iter().enumerate() {
+            // empty tiles are left transparent unless the theme has a background
+            if pixel != EMPTY || theme.background.is_some() {
+                image.set(i % width, i / width, colours[pixel as usize]);
+            }
+        }
+
+        let path = dir.join(format!("{:04}.png", current));
+        image.write_png(File::create(&path)?)?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DungeonBuilder;
+    use crate::dungeon::Algorithm;
+    use crate::record::{write_gif, Step};
+    use crate::theme::Theme;
+
+    #[test]
+    fn test_record_steps() {
+        let builder = DungeonBuilder::new()
+            .size(30, 20)
+            .algorithm(Algorithm::Bsp)
+            .seed("record")
+            .walls(true);
+        let level = builder.record().unwrap();
+        let frames = level.frames.as_ref().unwrap();
+
+        assert!(matches!(frames[0].step, Step::LeafSplit { .. }));
+        assert_eq!(
+            frames
+                .iter()
+                .filter(|frame| matches!(frame.step, Step::RoomPlaced(_)))
+                .count(),
+            level.rooms.len()
+        );
+        assert_eq!(
+            frames.last().map(|frame| frame.step),
+            Some(Step::WallsAdded)
+        );
+        assert_eq!(frames.last().map(|frame| &frame.board), Some(&level.board));
+        assert_eq!(level.board, builder.build().unwrap().board);
+    }
+
+    #[test]
+    fn test_gif() {
+        let level = DungeonBuilder::new()
+            .size(20, 10)
+            .seed("record")
+            .record()
+            .unwrap();
+        let mut bytes = vec![];
+        write_gif(&level, &Theme::default(), &mut bytes).unwrap();
+
+        assert_eq!(&bytes[..6], b"GIF89a");
+        // logical screen width and height
+        assert_eq!(&bytes[6..10], &[64, 1, 160, 0]);
+    }
+}
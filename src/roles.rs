@@ -0,0 +1,183 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde_derive::Serialize;
+
+use crate::graph::RoomGraph;
+use crate::level::Level;
+use crate::tile::Tile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoomRole {
+    Normal,
+    Entrance,
+    Boss,
+    Treasure,
+    Secret,
+}
+
+// Tags rooms using the layout: the entrance is a dead end (or where the stairs up are),
+// the boss is as far from it as possible, one other dead end with a single way in
+// is hidden behind a secret door, and any remaining dead ends hold treasure.
+pub fn assign_roles(level: &mut Level, rng: &mut Pcg64) {
+    if level.rooms.is_empty() {
+        return;
+    }
+
+    let graph = RoomGraph::new(level);
+    let leaves: Vec<usize> = (0..level.rooms.len())
+        .filter(|&room| graph.degree(room) == 1)
+        .collect();
+
+    let stairs = level
+        .board
+        .iter()
+        .enumerate()
+        .find_map(|(y, row)| {
+            row.iter()
+                .position(|&tile| tile == Tile::StairsUp)
+                .map(|x| (x as i32, y as i32))
+        })
+        .and_then(|(x, y)| level.room_at(x, y));
+
+    let entrance = match (stairs, leaves.choose(rng)) {
+        (Some(room), _) | (None, Some(&room)) => room,
+        (None, None) => 0,
+    };
+
+    let distances = graph.distances(entrance);
+    let boss = (0..level.rooms.len())
+        .filter(|&room| room != entrance)
+        .filter_map(|room| distances[room].map(|distance| (room, distance)))
+        .max_by_key(|&(room, distance)| {
+            (distance, level.rooms[room].width * level.rooms[room].height)
+        })
+        .map(|(room, _)| room);
+
+    let mut spare: Vec<usize> = leaves
+        .into_iter()
+        .filter(|&room| room != entrance && Some(room) != boss)
+        .collect();
+
+    // the way in has to be plain floor, not stairs to another floor or a door
+    let hidden = spare.iter().position(|&room| match graph.exits[room][..] {
+        [exit] => {
+            graph.links[room][0].door.is_some()
+                && level.board[exit.y as usize][exit.x as usize] == Tile::Walkable
+        }
+        _ => false,
+    });
+    if let Some(position) = hidden {
+        let room = spare.remove(position);
+        let door = graph.exits[room][0];
+        level.board[door.y as usize][door.x as usize] = Tile::SecretDoor;
+        level.rooms[room].role = RoomRole::Secret;
+    }
+
+    for room in spare {
+        level.rooms[room].role = RoomRole::Treasure;
+    }
+
+    level.rooms[entrance].role = RoomRole::Entrance;
+    if let Some(room) = boss {
+        level.rooms[room].role = RoomRole::Boss;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DungeonBuilder;
+    use crate::graph::RoomGraph;
+    use crate::level::fixtures::four_rooms;
+    use crate::level::Level;
+    use crate::roles::{assign_roles, RoomRole};
+    use crate::tile::Tile;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    fn with_role(level: &Level, role: RoomRole) -> Vec<usize> {
+        (0..level.rooms.len())
+            .filter(|&room| level.rooms[room].role == role)
+            .collect()
+    }
+
+    #[test]
+    fn test_assign_roles() {
+        for seed in 0..10 {
+            let mut level = four_rooms();
+            let graph = RoomGraph::new(&level);
+            let mut rng = Pcg64::seed_from_u64(seed);
+            assign_roles(&mut level, &mut rng);
+
+            let entrance = with_role(&level, RoomRole::Entrance);
+            let boss = with_role(&level, RoomRole::Boss);
+            let secret = with_role(&level, RoomRole::Secret);
+            assert_eq!(entrance.len(), 1);
+            assert_eq!(boss.len(), 1);
+            assert_eq!(secret.len(), 1);
+            assert_eq!(with_role(&level, RoomRole::Normal), vec![1]);
+
+            assert_eq!(graph.degree(entrance[0]), 1);
+            assert_eq!(graph.distances(entrance[0])[boss[0]], Some(2));
+
+            let door = graph.exits[secret[0]][0];
+            assert_eq!(
+                level.board[door.y as usize][door.x as usize],
+                Tile::SecretDoor
+            );
+        }
+    }
+
+    #[test]
+    fn test_entrance_at_stairs() {
+        let mut level = four_rooms();
+        level.board[2][13] = Tile::StairsUp;
+        let mut rng = Pcg64::seed_from_u64(1);
+        assign_roles(&mut level, &mut rng);
+
+        assert_eq!(level.rooms[1].role, RoomRole::Entrance);
+    }
+
+    #[test]
+    fn test_secret_door_on_floor() {
+        for seed in 0..10 {
+            // stairs and a locked door in the way of two of the three dead ends
+            let mut level = four_rooms();
+            level.board[2][5] = Tile::StairsDown;
+            level.board[8][14] = Tile::Door;
+            let mut rng = Pcg64::seed_from_u64(seed);
+            assign_roles(&mut level, &mut rng);
+
+            assert_eq!(level.board[2][5], Tile::StairsDown);
+            assert_eq!(level.board[8][14], Tile::Door);
+            for room in with_role(&level, RoomRole::Secret) {
+                assert_eq!(room, 2);
+                assert_eq!(level.board[3][23], Tile::SecretDoor);
+            }
+        }
+    }
+
+    #[test]
+    fn test_roles_on_floors() {
+        for seed in 0..20 {
+            let builder = DungeonBuilder::new()
+                .seed(seed.to_string().as_str())
+                .walls(true)
+                .floors(3);
+            let plain = builder.build_dungeon().unwrap();
+            let tagged = builder.roles(true).build_dungeon().unwrap();
+
+            // roles only ever turn floor into a secret door, leaving stairs linking floors
+            for (level, expected) in tagged.floors.iter().zip(&plain.floors) {
+                for (row, expected) in level.board.iter().zip(&expected.board) {
+                    for (&tile, &expected) in row.iter().zip(expected) {
+                        match tile {
+                            Tile::SecretDoor => assert_eq!(expected, Tile::Walkable),
+                            _ => assert_eq!(tile, expected),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
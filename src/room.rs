@@ -1,13 +1,15 @@
 use serde_derive::Serialize;
 
+use crate::roles::RoomRole;
 use crate::tile::Tile;
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
 }
 
+#[allow(unused_macros)]
 macro_rules! room {
     (
         $([$( $x:expr ),*]),*
@@ -30,6 +32,7 @@ pub struct Room {
     pub height: i32,
     pub centre: Point,
     pub layout: Vec<Vec<Tile>>,
+    pub role: RoomRole,
 }
 
 impl Room {
@@ -59,6 +62,7 @@ impl Room {
                 y: (y + height / 2),
             },
             layout: tiles,
+            role: RoomRole::Normal,
         }
     }
 
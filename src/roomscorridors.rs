@@ -1,8 +1,9 @@
 use crate::level::Level;
+use crate::record::{Area, Step};
 use crate::room::Room;
 use crate::tile::Tile;
-use rand::rngs::StdRng;
 use rand::Rng;
+use rand_pcg::Pcg64;
 
 pub struct RoomsCorridors {
     level: Level,
@@ -13,13 +14,17 @@ impl RoomsCorridors {
         width: i32,
         height: i32,
         hash: &str,
-        rng: &mut StdRng,
+        rng: &mut Pcg64,
         add_walls: bool,
         min_room_width: i32,
         min_room_height: i32,
     ) -> Level {
         let level = Level::new(width, height, hash, min_room_width, min_room_height);
+        RoomsCorridors::generate(level, rng, add_walls)
+    }
 
+    // fills in an empty level, eg one which is recording its steps
+    pub fn generate(level: Level, rng: &mut Pcg64, add_walls: bool) -> Level {
         let mut map = RoomsCorridors { level };
 
         map.place_rooms(rng);
@@ -36,28 +41,44 @@ impl RoomsCorridors {
         for col in start_x..end_x + 1 {
             self.level.board[y as usize][col as usize] = Tile::Walkable;
         }
+        self.level.record(Step::CorridorCarved(Area {
+            x: start_x,
+            y,
+            width: end_x - start_x + 1,
+            height: 1,
+        }));
     }
 
     fn vert_corridor(&mut self, start_y: i32, end_y: i32, x: i32) {
         for row in start_y..end_y + 1 {
             self.level.board[row as usize][x as usize] = Tile::Walkable;
         }
+        self.level.record(Step::CorridorCarved(Area {
+            x,
+            y: start_y,
+            width: 1,
+            height: end_y - start_y + 1,
+        }));
     }
 
-    fn place_rooms(&mut self, rng: &mut StdRng) {
+    fn place_rooms(&mut self, rng: &mut Pcg64) {
         let max_rooms = 10;
 
         // let min_room_width = 4;
-        let max_room_width = 8;
+        let max_room_width = 8.max(self.level.min_room_width.saturating_add(1));
         // let min_room_height = 5;
-        let max_room_height = 12;
+        let max_room_height = 12.max(self.level.min_room_height.saturating_add(1));
 
-        // TODO fix out of bounds
         for _ in 0..max_rooms {
             let mut x = rng.gen_range(0..self.level.width);
             let mut y = rng.gen_range(0..self.level.height);
-            let width = rng.gen_range(self.level.min_room_width..max_room_width);
-            let height = rng.gen_range(self.level.min_room_height..max_room_height);
+            // rooms can't be bigger than the level
+            let width = rng
+                .gen_range(self.level.min_room_width..max_room_width)
+                .min(self.level.width);
+            let height = rng
+                .gen_range(self.level.min_room_height..max_room_height)
+                .min(self.level.height);
 
             if x + width > self.level.width {
                 x = self.level.width - width;
@@ -80,13 +101,11 @@ impl RoomsCorridors {
             if !collides {
                 self.level.add_room(&room);
             }
-
-            // draw(&self, "./img", format!("0{}", i + 1).as_str()).unwrap();
         }
     }
 
-    fn place_corridors(&mut self, rng: &mut StdRng) {
-        for i in 0..(self.level.rooms.len() - 1) {
+    fn place_corridors(&mut self, rng: &mut Pcg64) {
+        for i in 0..self.level.rooms.len().saturating_sub(1) {
             let room = self.level.rooms[i].clone();
             let other = self.level.rooms[i + 1].clone();
 
@@ -113,8 +132,6 @@ impl RoomsCorridors {
                     }
                 }
             }
-
-            // draw(&self, "./img", format!("{}", i + 11).as_str()).unwrap();
         }
     }
 }
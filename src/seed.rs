@@ -0,0 +1,130 @@
+use rand::distributions::Alphanumeric;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+pub fn create_hash(text: &str) -> String {
+    let mut hasher = Sha256::default();
+    hasher.update(text.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+// Everything random about a level comes from its seed, which is always a SHA-256 hash.
+// The hash seeds a PCG generator, which gives the same numbers on every platform, so a
+// seed can be shared and the level recreated. Ranges, shuffles and picks come from rand,
+// which can change them between its releases, so levels only stay the same with the
+// versions in Cargo.lock; the golden tests catch an upgrade which changes them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seed {
+    hash: String,
+}
+
+impl Seed {
+    // Hashes (as printed by the generator) are used as they are, anything else is hashed
+    pub fn new(text: &str) -> Seed {
+        if text.len() == 64 && text.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Seed {
+                hash: text.to_lowercase(),
+            };
+        }
+
+        Seed::from_text(text)
+    }
+
+    pub fn from_text(text: &str) -> Seed {
+        Seed {
+            hash: create_hash(text),
+        }
+    }
+
+    pub fn random() -> Seed {
+        let text: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+
+        Seed::from_text(&text)
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    // seed for the nth level made from this one, eg each floor of a dungeon
+    pub fn derive(&self, index: usize) -> Seed {
+        Seed::from_text(&format!("{}-{}", self.hash, index))
+    }
+
+    pub fn rng(&self) -> Pcg64 {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&self.hash[i * 2..i * 2 + 2], 16).expect("Invalid hash");
+        }
+
+        Pcg64::from_seed(bytes)
+    }
+}
+
+impl From<&str> for Seed {
+    fn from(text: &str) -> Seed {
+        Seed::new(text)
+    }
+}
+
+impl From<u64> for Seed {
+    fn from(number: u64) -> Seed {
+        Seed::from_text(&number.to_string())
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::seed::{create_hash, Seed};
+    use rand::Rng;
+
+    #[test]
+    fn test_create_hash() {
+        assert_eq!(
+            create_hash("hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(Seed::new("hello").hash(), hash);
+        assert_eq!(Seed::new(hash).hash(), hash);
+        assert_eq!(Seed::new(&hash.to_uppercase()).hash(), hash);
+        assert_eq!(Seed::from(42), Seed::new("42"));
+        assert_ne!(Seed::from_text(hash).hash(), hash);
+        assert_eq!(Seed::new("").hash().len(), 64);
+    }
+
+    #[test]
+    fn test_derive() {
+        let seed = Seed::new("hello");
+        assert_eq!(seed.derive(1), seed.derive(1));
+        assert_ne!(seed.derive(1), seed.derive(2));
+        assert_ne!(seed.derive(1), seed);
+    }
+
+    // pins the generator, so a change which would alter every level is noticed
+    #[test]
+    fn test_rng_is_stable() {
+        let mut rng = Seed::new("hello").rng();
+        let numbers: Vec<u32> = (0..4).map(|_| rng.gen()).collect();
+        assert_eq!(
+            numbers,
+            vec![4082761843, 3244612221, 2547802951, 2723413430]
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use serde_json::{Map, Value};
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::config::Config;
+use crate::draw::png_bytes;
+use crate::level::TILE_SIZE;
+use crate::output::OutputFormat;
+use crate::seed::Seed;
+use crate::text::GLYPH_PRESETS;
+use crate::theme::PRESETS;
+
+// settings which write or read files, which a request can't change, so clients can't
+// look around the server's filesystem
+const FILE_SETTINGS: [&str; 8] = [
+    "output", "draw", "image", "svg", "record", "import", "populate", "palette",
+];
+
+// settings which take a preset or the path to a file, which a request can only set to
+// a preset
+const PRESET_SETTINGS: [(&str, &[&str]); 2] = [("theme", &PRESETS), ("glyphs", &GLYPH_PRESETS)];
+
+// settings which are always text, so a seed like `123` isn't read as a number
+const TEXT_SETTINGS: [&str; 6] = ["seed", "text", "populate", "glyphs", "theme", "palette"];
+
+// Requests are answered one at a time, so these keep the largest level a request can
+// ask for quick enough that it doesn't hold up other clients for long.
+
+// largest width or height a request can ask for
+const MAX_SIZE: i32 = 200;
+
+// the most floors and locked doors a request can ask for, and pixels per tile in a png
+const MAX_FLOORS: usize = 3;
+const MAX_LOCKS: usize = 5;
+const MAX_TILE_SIZE: i32 = 32;
+
+// largest png a request can ask for, which is drawn in memory at four bytes a pixel
+const MAX_PIXELS: i64 = 2048 * 2048;
+
+// largest JSON body read from a request
+const MAX_BODY: u64 = 64 * 1024;
+
+// What is sent back for a request: the level, or an error message.
+#[derive(Debug)]
+pub struct Reply {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+    // the seed the level was made from, so a random level can be made again
+    pub seed: Option<String>,
+}
+
+impl Reply {
+    fn error(status: u16, message: &str) -> Reply {
+        Reply {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", message).into_bytes(),
+            seed: None,
+        }
+    }
+}
+
+fn bad_request<E: ToString>(err: E) -> Reply {
+    Reply::error(400, &err.to_string())
+}
+
+// query values are read as JSON if they can be, eg `walls=true` or `prune=0.5`, and as
+// text otherwise, eg `algorithm=bsp`. A setting with no value, eg `?walls`, is turned on.
+fn query_settings(query: &str) -> Map<String, Value> {
+    form_urlencoded::parse(query.as_bytes())
+        .map(|(key, value)| {
+            let value = if value.is_empty() {
+                Value::Bool(true)
+            } else if TEXT_SETTINGS.contains(&key.as_ref()) {
+                Value::String(value.to_string())
+            } else {
+                serde_json::from_str(&value).unwrap_or_else(|_| Value::String(value.to_string()))
+            };
+
+            (key.to_string(), value)
+        })
+        .collect()
+}
+
+// The settings a request asks for laid over the server's, and whether it wants a png.
+// The request's settings are the same as in a config file, plus `png` as a format.
+pub fn request_config(
+    base: &Config,
+    settings: Map<String, Value>,
+) -> Result<(Config, bool), String> {
+    let mut merged = match serde_json::to_value(base).map_err(|err| err.to_string())? {
+        Value::Object(merged) => merged,
+        _ => unreachable!("config is always a JSON object"),
+    };
+
+    let mut png = false;
+    for (key, value) in settings {
+        if FILE_SETTINGS.contains(&key.as_str()) {
+            return Err(format!("{} can't be set by a request", key));
+        }
+        if let Some((_, presets)) = PRESET_SETTINGS.iter().find(|(name, _)| *name == key) {
+            match &value {
+                Value::Null => {}
+                Value::String(name) if presets.contains(&name.as_str()) => {}
+                _ => return Err(format!("{} must be one of {}", key, presets.join(", "))),
+            }
+        }
+        if key == "format" && value == "png" {
+            png = true;
+            continue;
+        }
+        merged.insert(key, value);
+    }
+
+    let config: Config =
+        serde_json::from_value(Value::Object(merged)).map_err(|err| err.to_string())?;
+    if config.width > MAX_SIZE || config.height > MAX_SIZE {
+        return Err(format!("levels can be at most {}x{}", MAX_SIZE, MAX_SIZE));
+    }
+    if config.min_room_width > config.width || config.min_room_height > config.height {
+        return Err("rooms can be at most the size of the level".to_string());
+    }
+    if config.floors > MAX_FLOORS {
+        return Err(format!("levels can have at most {} floors", MAX_FLOORS));
+    }
+    if config.locks > MAX_LOCKS {
+        return Err(format!("levels can have at most {} locks", MAX_LOCKS));
+    }
+    if let Some(size) = config.tile_size {
+        if !(1..=MAX_TILE_SIZE).contains(&size) {
+            return Err(format!("tile size must be from 1 to {}", MAX_TILE_SIZE));
+        }
+    }
+    if png {
+        let theme = config.theme().map_err(|err| err.to_string())?;
+        let tile_size = theme.tile_size.unwrap_or(TILE_SIZE) as i64;
+        let pixels = config.width as i64 * config.height as i64 * tile_size * tile_size;
+        if pixels > MAX_PIXELS {
+            return Err(format!(
+                "pngs can be at most {} pixels, so use a smaller level or tile_size",
+                MAX_PIXELS
+            ));
+        }
+    }
+
+    Ok((config, png))
+}
+
+// Makes the level for a request, `GET /level?...` with settings in the query or
+// `POST /level` with them as a JSON object.
+pub fn reply(base: &Config, method: &Method, url: &str, body: &str) -> Reply {
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (url, ""),
+    };
+    if path != "/level" {
+        return Reply::error(404, "not found, levels are made at /level");
+    }
+
+    let settings = match method {
+        Method::Get => query_settings(query),
+        Method::Post if body.trim().is_empty() => Map::new(),
+        Method::Post => match serde_json::from_str(body) {
+            Ok(settings) => settings,
+            Err(err) => return bad_request(format!("couldn't read settings: {}", err)),
+        },
+        _ => return Reply::error(405, "levels are made with GET or POST"),
+    };
+    let (config, png) = match request_config(base, settings) {
+        Ok(request) => request,
+        Err(err) => return bad_request(err),
+    };
+
+    let seed = config.seed().unwrap_or_else(Seed::random);
+    let dungeon = match config.builder() {
+        Ok(builder) => builder.seed(seed.clone()).build_dungeon(),
+        Err(err) => return bad_request(err),
+    };
+    let dungeon = match dungeon {
+        Ok(dungeon) => dungeon,
+        Err(err) => return bad_request(err),
+    };
+
+    // images show the first floor
+    let (content_type, body) = if png {
+        let image = config
+            .theme()
+            .and_then(|theme| png_bytes(&dungeon.floors[0], &theme));
+        match image {
+            Ok(image) => ("image/png", image),
+            Err(err) => return bad_request(err),
+        }
+    } else {
+        let document = config
+            .format_options()
+            .and_then(|options| Ok(config.format.dungeon(&dungeon, &options)?));
+        let content_type = match config.format {
+            OutputFormat::Text => "text/plain; charset=utf-8",
+            OutputFormat::Json => "application/json",
+            OutputFormat::Csv => "text/csv; charset=utf-8",
+        };
+        match document {
+            Ok(document) => (content_type, document.into_bytes()),
+            Err(err) => return bad_request(err),
+        }
+    };
+
+    Reply {
+        status: 200,
+        content_type,
+        body,
+        seed: Some(seed.to_string()),
+    }
+}
+
+fn respond(base: &Config, mut request: Request) -> io::Result<()> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY)
+        .read_to_string(&mut body)?;
+    // a request which panics gets an error, rather than stopping the server
+    let reply = panic::catch_unwind(AssertUnwindSafe(|| {
+        reply(base, request.method(), request.url(), &body)
+    }))
+    .unwrap_or_else(|_| Reply::error(500, "couldn't create the level"));
+
+    let mut response = Response::from_data(reply.body)
+        .with_status_code(reply.status)
+        .with_header(header("Content-Type", reply.content_type));
+    if let Some(seed) = &reply.seed {
+        response.add_header(header("X-Dungeon-Seed", seed));
+    }
+
+    request.respond(response)
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("header is valid")
+}
+
+// Answers requests one at a time until the process is stopped. The config given is used
+// for any settings a request leaves out.
+pub fn serve(base: &Config, address: &str) -> io::Result<()> {
+    let server = Server::http(address).map_err(io::Error::other)?;
+    eprintln!("Serving levels at http://{}/level", server.server_addr());
+
+    for request in server.incoming_requests() {
+        // a client hanging up shouldn't stop the server
+        if let Err(err) = respond(base, request) {
+            eprintln!("Couldn't send response: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tiny_http::{Method, Server};
+
+    use crate::builder::DungeonBuilder;
+    use crate::config::Config;
+    use crate::dungeon::Algorithm;
+    use crate::serve::{reply, respond};
+
+    #[test]
+    fn test_query() {
+        let base = Config::default();
+        let reply = reply(
+            &base,
+            &Method::Get,
+            "/level?seed=123&algorithm=bsp&width=30&height=20&walls&format=json",
+            "",
+        );
+        let level = DungeonBuilder::new()
+            .seed("123")
+            .algorithm(Algorithm::Bsp)
+            .size(30, 20)
+            .walls(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.content_type, "application/json");
+        assert_eq!(reply.seed.as_deref(), Some(level.hash.as_str()));
+        assert_eq!(
+            String::from_utf8(reply.body).unwrap(),
+            format!("{}\n", serde_json::to_string(&level).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_json_body() {
+        let base = Config::default();
+        let text = reply(
+            &base,
+            &Method::Post,
+            "/level",
+            r#"{"seed": "body", "width": 20, "height": 20}"#,
+        );
+        let png = reply(
+            &base,
+            &Method::Post,
+            "/level",
+            r#"{"seed": "body", "format": "png"}"#,
+        );
+
+        assert_eq!(text.status, 200);
+        assert_eq!(text.content_type, "text/plain; charset=utf-8");
+        assert_eq!(text.body.iter().filter(|&&byte| byte == b'\n').count(), 20);
+        assert_eq!(png.content_type, "image/png");
+        assert!(png.body.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let base = Config::default();
+        let status =
+            |method: Method, url: &str, body: &str| reply(&base, &method, url, body).status;
+
+        assert_eq!(status(Method::Get, "/", ""), 404);
+        assert_eq!(status(Method::Delete, "/level", ""), 405);
+        assert_eq!(status(Method::Get, "/level?size=3", ""), 400);
+        assert_eq!(status(Method::Get, "/level?width=0", ""), 400);
+        assert_eq!(status(Method::Get, "/level?width=100000", ""), 400);
+        assert_eq!(status(Method::Get, "/level?output=/tmp/level", ""), 400);
+        assert_eq!(status(Method::Get, "/level?populate=/etc/passwd", ""), 400);
+        assert_eq!(status(Method::Get, "/level?palette=/etc/passwd", ""), 400);
+        assert_eq!(status(Method::Get, "/level?theme=/etc/passwd", ""), 400);
+        assert_eq!(status(Method::Get, "/level?glyphs=/etc/passwd", ""), 400);
+        assert_eq!(status(Method::Get, "/level?theme=dark&glyphs=box", ""), 200);
+        assert_eq!(status(Method::Get, "/level?width=201", ""), 400);
+        assert_eq!(status(Method::Get, "/level?floors=4", ""), 400);
+        assert_eq!(status(Method::Get, "/level?locks=6", ""), 400);
+        assert_eq!(status(Method::Get, "/level?min_room_width=100", ""), 400);
+        assert_eq!(status(Method::Get, "/level?tile_size=-4", ""), 400);
+        assert_eq!(status(Method::Get, "/level?tile_size=1000", ""), 400);
+        assert_eq!(
+            status(Method::Get, "/level?format=png&width=200&height=200", ""),
+            400
+        );
+        assert_eq!(
+            status(Method::Get, "/level?format=png&tile_size=8", ""),
+            200
+        );
+        assert_eq!(status(Method::Post, "/level", "[1, 2]"), 400);
+    }
+
+    // the largest levels a request can ask for shouldn't keep other clients waiting
+    #[test]
+    fn test_largest_request() {
+        let largest = "/level?width=200&height=200&floors=3&locks=5&walls&roles&prune=0";
+        for algorithm in ["rooms", "bsp"].iter() {
+            for format in ["json", "png&tile_size=10"].iter() {
+                let url = format!("{}&algorithm={}&format={}", largest, algorithm, format);
+                let start = Instant::now();
+                let reply = reply(&Config::default(), &Method::Get, &url, "");
+
+                assert_eq!(reply.status, 200, "{}", url);
+                assert!(start.elapsed() < Duration::from_secs(5), "{}", url);
+            }
+        }
+    }
+
+    #[test]
+    fn test_localhost() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let handle = thread::spawn(move || {
+            let request = server.recv().unwrap();
+            respond(&Config::default(), request).unwrap();
+        });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /level?seed=local&format=csv HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        handle.join().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("Content-Type: text/csv"));
+        assert!(response.contains("X-Dungeon-Seed: "));
+    }
+}
@@ -0,0 +1,345 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::level::Level;
+use crate::roles::RoomRole;
+use crate::room::Point;
+use crate::theme::{Colour, Theme};
+use crate::tile::Tile;
+
+// Extra layers drawn over the tiles
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SvgOptions {
+    pub outlines: bool,
+    pub room_ids: bool,
+    pub grid: bool,
+    pub markers: bool,
+}
+
+// Each kind of tile is one path, made of the outlines of the areas it covers rather than
+// a square per tile, so the file stays small and changes to a level show up as small diffs.
+// Coordinates are in tiles and scaled up by the level's tile size.
+pub fn to_svg(level: &Level, options: &SvgOptions, theme: &Theme) -> String {
+    let mut svg = String::new();
+    let scale = theme.tile_size.unwrap_or(level.tile_size);
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+        level.width * scale,
+        level.height * scale,
+        level.width,
+        level.height
+    )
+    .unwrap();
+
+    if let Some(colour) = theme.background {
+        writeln!(
+            svg,
+            r#"<rect class="background" fill="{}" width="{}" height="{}"/>"#,
+            colour.hex(),
+            level.width,
+            level.height
+        )
+        .unwrap();
+    }
+
+    let classes = tile_classes(level);
+    for (class, colour) in colours(theme).iter() {
+        let path = outline(&classes, class);
+        if !path.is_empty() {
+            writeln!(
+                svg,
+                r#"<path class="{}" fill="{}" d="{}"/>"#,
+                class,
+                colour.hex(),
+                path
+            )
+            .unwrap();
+        }
+    }
+
+    for lock in &level.locks {
+        writeln!(
+            svg,
+            r#"<rect class="key" fill="{}" x="{}.25" y="{}.25" width="0.5" height="0.5"/>"#,
+            theme.key.hex(),
+            lock.key.x,
+            lock.key.y
+        )
+        .unwrap();
+    }
+
+    if options.grid {
+        let mut path = String::new();
+        for x in 0..=level.width {
+            write!(path, "M{} 0V{}", x, level.height).unwrap();
+        }
+        for y in 0..=level.height {
+            write!(path, "M0 {}H{}", y, level.width).unwrap();
+        }
+        writeln!(
+            svg,
+            r#"<path class="grid" fill="none" stroke="{}" stroke-width="{}" d="{}"/>"#,
+            theme.grid.unwrap_or(GREY).hex(),
+            theme.grid_width / scale as f64,
+            path
+        )
+        .unwrap();
+    }
+
+    // the theme's outline colour, or a dark grey if it doesn't set one
+    let ink = theme.outline.unwrap_or(DARK).hex();
+    if options.outlines {
+        for (index, room) in level.rooms.iter().enumerate() {
+            writeln!(
+                svg,
+                r#"<rect class="room" id="room-{}" fill="none" stroke="{}" stroke-width="{}" x="{}" y="{}" width="{}" height="{}"/>"#,
+                index,
+                ink,
+                theme.outline_width / scale as f64,
+                room.x,
+                room.y,
+                room.width,
+                room.height
+            )
+            .unwrap();
+        }
+    }
+
+    if options.room_ids {
+        for (index, room) in level.rooms.iter().enumerate() {
+            writeln!(
+                svg,
+                r#"<text class="room-id" fill="{}" font-size="1" font-family="sans-serif" text-anchor="middle" dominant-baseline="central" x="{}.5" y="{}.5">{}</text>"#,
+                ink,
+                room.centre.x,
+                room.centre.y,
+                index
+            )
+            .unwrap();
+        }
+    }
+
+    // one path for each depth of the BSP tree, so earlier splits can be drawn thicker
+    if let Some(tree) = &level.tree {
+        let mut depths: BTreeMap<usize, String> = BTreeMap::new();
+        for partition in tree.iter() {
+            if let Some(((x1, y1), (x2, y2))) = partition.line() {
+                let path = depths.entry(partition.depth).or_default();
+                write!(path, "M{} {}L{} {}", x1, y1, x2, y2).unwrap();
+            }
+        }
+
+        for (depth, path) in depths {
+            writeln!(
+                svg,
+                r#"<path class="tree" data-depth="{}" fill="none" stroke="{}" stroke-width="{}" d="{}"/>"#,
+                depth,
+                theme.tree.hex(),
+                (4.0 - depth as f64).max(1.0) / scale as f64,
+                path
+            )
+            .unwrap();
+        }
+    }
+
+    if options.markers {
+        if let Some(start) = level.start() {
+            marker(&mut svg, "start", theme.stairs_up, &start);
+        }
+        if let Some(exit) = level.exit() {
+            marker(&mut svg, "exit", theme.stairs_down, &exit);
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+const GREY: Colour = Colour::new(204, 204, 204);
+const DARK: Colour = Colour::new(32, 33, 36);
+
+// classes in the order they're drawn
+fn colours(theme: &Theme) -> [(&'static str, Colour); 10] {
+    [
+        ("floor", theme.floor),
+        ("wall", theme.wall),
+        ("entrance", theme.entrance),
+        ("boss", theme.boss),
+        ("treasure", theme.treasure),
+        ("secret", theme.secret),
+        ("stairs-up", theme.stairs_up),
+        ("stairs-down", theme.stairs_down),
+        ("door", theme.door),
+        ("secret-door", theme.secret_door),
+    ]
+}
+
+// what each tile is drawn as; floor in a room with a role takes the role's colour
+fn tile_classes(level: &Level) -> Vec<Vec<Option<&'static str>>> {
+    let rooms = level.room_map();
+    let mut classes = vec![vec![None; level.width as usize]; level.height as usize];
+
+    for (y, row) in level.board.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            classes[y][x] = match tile {
+                Tile::Empty => None,
+                Tile::Walkable => match rooms[y][x].map(|room| level.rooms[room].role) {
+                    Some(RoomRole::Entrance) => Some("entrance"),
+                    Some(RoomRole::Boss) => Some("boss"),
+                    Some(RoomRole::Treasure) => Some("treasure"),
+                    Some(RoomRole::Secret) => Some("secret"),
+                    _ => Some("floor"),
+                },
+                Tile::Wall => Some("wall"),
+                Tile::StairsUp => Some("stairs-up"),
+                Tile::StairsDown => Some("stairs-down"),
+                Tile::Door => Some("door"),
+                Tile::SecretDoor => Some("secret-door"),
+            };
+        }
+    }
+
+    classes
+}
+
+// Traces the edges between tiles of a class and anything else, going clockwise round each
+// area (and anticlockwise round holes in it), then joins them into closed loops.
+fn outline(classes: &[Vec<Option<&str>>], class: &str) -> String {
+    let is_class = |x: i32, y: i32| {
+        y >= 0
+            && x >= 0
+            && (y as usize) < classes.len()
+            && (x as usize) < classes[y as usize].len()
+            && classes[y as usize][x as usize] == Some(class)
+    };
+
+    let mut edges: BTreeMap<(i32, i32), Vec<(i32, i32)>> = BTreeMap::new();
+    for (y, row) in classes.iter().enumerate() {
+        for x in 0..row.len() {
+            let (x, y) = (x as i32, y as i32);
+            if !is_class(x, y) {
+                continue;
+            }
+
+            let sides = [
+                (is_class(x, y - 1), (x, y), (x + 1, y)),
+                (is_class(x + 1, y), (x + 1, y), (x + 1, y + 1)),
+                (is_class(x, y + 1), (x + 1, y + 1), (x, y + 1)),
+                (is_class(x - 1, y), (x, y + 1), (x, y)),
+            ];
+            for &(shared, from, to) in &sides {
+                if !shared {
+                    edges.entry(from).or_default().push(to);
+                }
+            }
+        }
+    }
+
+    let mut path = String::new();
+    while let Some((&start, _)) = edges.iter().next() {
+        let mut points = vec![start];
+        let mut current = start;
+        while let Some(ends) = edges.get_mut(&current) {
+            let next = ends.remove(0);
+            if edges[&current].is_empty() {
+                edges.remove(&current);
+            }
+            if next == start {
+                break;
+            }
+            points.push(next);
+            current = next;
+        }
+
+        write_loop(&mut path, &corners(&points));
+    }
+
+    path
+}
+
+// drops points in the middle of straight lines
+fn corners(points: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let count = points.len();
+    (0..count)
+        .filter(|&i| {
+            let (px, py) = points[(i + count - 1) % count];
+            let (x, y) = points[i];
+            let (nx, ny) = points[(i + 1) % count];
+            (x - px) * (ny - y) != (y - py) * (nx - x)
+        })
+        .map(|i| points[i])
+        .collect()
+}
+
+fn write_loop(path: &mut String, points: &[(i32, i32)]) {
+    for (i, (x, y)) in points.iter().enumerate() {
+        let command = if i == 0 { 'M' } else { 'L' };
+        write!(path, "{}{} {}", command, x, y).unwrap();
+    }
+    path.push('Z');
+}
+
+fn marker(svg: &mut String, class: &str, colour: Colour, point: &Point) {
+    writeln!(
+        svg,
+        r##"<circle class="{}" fill="{}" stroke="#ffffff" stroke-width="0.1" cx="{}.5" cy="{}.5" r="0.4"/>"##,
+        class,
+        colour.hex(),
+        point.x,
+        point.y
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::Level;
+    use crate::room::Room;
+    use crate::svg::{to_svg, SvgOptions};
+    use crate::theme::Theme;
+    use crate::tile::Tile;
+
+    #[test]
+    fn test_room_outline() {
+        let mut level = Level::new(6, 6, "svg", 3, 3);
+        level.add_room(&Room::new(1, 1, 3, 3, None));
+        let svg = to_svg(&level, &SvgOptions::default(), &Theme::default());
+
+        assert!(svg.contains(r##"<path class="floor" fill="#4286f4" d="M1 1L4 1L4 4L1 4Z"/>"##));
+        assert!(!svg.contains("wall"));
+    }
+
+    #[test]
+    fn test_hole() {
+        let mut level = Level::new(5, 5, "svg", 3, 3);
+        level.add_room(&Room::new(1, 1, 3, 3, None));
+        level.board[2][2] = Tile::Wall;
+        let svg = to_svg(&level, &SvgOptions::default(), &Theme::default());
+
+        assert!(svg.contains(r#"d="M1 1L4 1L4 4L1 4ZM2 2L2 3L3 3L3 2Z""#));
+        assert!(svg.contains(r##"<path class="wall" fill="#f48642" d="M2 2L3 2L3 3L2 3Z"/>"##));
+    }
+
+    #[test]
+    fn test_options() {
+        let mut level = Level::new(6, 6, "svg", 3, 3);
+        level.add_room(&Room::new(1, 1, 3, 3, None));
+        let options = SvgOptions {
+            outlines: true,
+            room_ids: true,
+            grid: true,
+            markers: true,
+        };
+        let svg = to_svg(&level, &options, &Theme::default());
+
+        assert!(svg.contains(r#"id="room-0""#));
+        assert!(svg.contains(r#"class="room-id""#));
+        assert!(svg.contains(r#"class="grid""#));
+        assert!(svg.contains(r#"class="start""#));
+        assert!(svg.contains(r#"class="exit""#));
+    }
+}
@@ -0,0 +1,271 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::from_str;
+use std::fmt::Write;
+use std::fs;
+
+use crate::entity::EntityKind;
+use crate::level::Level;
+use crate::theme::{Colour, Theme};
+use crate::tile::Tile;
+
+// Characters used to draw a level as text. Settings missing from a glyph file are taken
+// from the classic set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Glyphs {
+    pub empty: char,
+    pub floor: char,
+    pub wall: char,
+    pub stairs_up: char,
+    pub stairs_down: char,
+    pub door: char,
+    pub secret_door: char,
+    // walls are drawn with box-drawing lines joining up with the walls next to them
+    pub box_walls: bool,
+    // monsters are drawn with the first letter of their name if unset
+    pub monster: Option<char>,
+    pub item: char,
+    pub trap: char,
+    pub key: char,
+}
+
+impl Default for Glyphs {
+    fn default() -> Glyphs {
+        Glyphs {
+            empty: ' ',
+            floor: '.',
+            wall: '#',
+            stairs_up: '<',
+            stairs_down: '>',
+            door: '+',
+            secret_door: '*',
+            box_walls: false,
+            monster: None,
+            item: '!',
+            trap: '^',
+            key: 'k',
+        }
+    }
+}
+
+pub const GLYPH_PRESETS: [&str; 3] = ["classic", "box", "digits"];
+
+impl Glyphs {
+    pub fn preset(name: &str) -> Option<Glyphs> {
+        let glyphs = match name {
+            "classic" => Glyphs::default(),
+            "box" => Glyphs {
+                box_walls: true,
+                ..Glyphs::default()
+            },
+            // the tile numbers, as in JSON and CSV output
+            "digits" => Glyphs {
+                empty: '0',
+                floor: '1',
+                wall: '2',
+                stairs_up: '3',
+                stairs_down: '4',
+                door: '5',
+                secret_door: '6',
+                ..Glyphs::default()
+            },
+            _ => return None,
+        };
+
+        Some(glyphs)
+    }
+
+    pub fn load(path: &str) -> std::io::Result<Glyphs> {
+        let glyphs = fs::read_to_string(path)?;
+        Ok(from_str(&glyphs)?)
+    }
+
+    // a preset, or a glyph file if there's no preset with that name
+    pub fn find(name: &str) -> std::io::Result<Glyphs> {
+        match Glyphs::preset(name) {
+            Some(glyphs) => Ok(glyphs),
+            None => Glyphs::load(name),
+        }
+    }
+
+    pub fn tile(&self, tile: Tile) -> char {
+        match tile {
+            Tile::Empty => self.empty,
+            Tile::Walkable => self.floor,
+            Tile::Wall => self.wall,
+            Tile::StairsUp => self.stairs_up,
+            Tile::StairsDown => self.stairs_down,
+            Tile::Door => self.door,
+            Tile::SecretDoor => self.secret_door,
+        }
+    }
+}
+
+// Draws the level one character per tile, with keys and entities over the tiles they're
+// on. If a theme is given each character is coloured to match it with ANSI escape codes.
+pub fn to_text(level: &Level, glyphs: &Glyphs, colours: Option<&Theme>) -> String {
+    let rooms = level.room_map();
+    let mut cells: Vec<Vec<(char, Option<Colour>)>> = level
+        .board
+        .iter()
+        .enumerate()
+        .map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(x, &tile)| {
+                    let glyph = match tile {
+                        Tile::Wall if glyphs.box_walls => box_wall(&level.board, x, y),
+                        _ => glyphs.tile(tile),
+                    };
+                    let colour = colours.and_then(|theme| {
+                        let role = rooms[y][x].map(|room| level.rooms[room].role);
+                        match (tile, role.and_then(|role| theme.role(role))) {
+                            (Tile::Walkable, Some(colour)) => Some(colour),
+                            _ => theme.tile(tile),
+                        }
+                    });
+
+                    (glyph, colour)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut overlay = |x: i32, y: i32, glyph: char, colour: Option<Colour>| {
+        if let Some(cell) = cells
+            .get_mut(y as usize)
+            .and_then(|row| row.get_mut(x as usize))
+        {
+            *cell = (glyph, colour);
+        }
+    };
+
+    for lock in &level.locks {
+        overlay(
+            lock.key.x,
+            lock.key.y,
+            glyphs.key,
+            colours.map(|theme| theme.key),
+        );
+    }
+
+    for entity in &level.entities {
+        let (glyph, colour) = match entity.kind {
+            EntityKind::Monster => (
+                glyphs
+                    .monster
+                    .or_else(|| entity.name.chars().next())
+                    .unwrap_or('M'),
+                colours.map(|theme| theme.boss),
+            ),
+            EntityKind::Item => (glyphs.item, colours.map(|theme| theme.treasure)),
+            EntityKind::Trap => (glyphs.trap, colours.map(|theme| theme.secret)),
+        };
+        overlay(entity.x, entity.y, glyph, colour);
+    }
+
+    let mut text = String::new();
+    for row in cells {
+        let mut current = None;
+        for (glyph, colour) in row {
+            if colour != current {
+                match colour {
+                    Some(colour) => {
+                        write!(text, "\x1b[38;2;{};{};{}m", colour.r, colour.g, colour.b).unwrap()
+                    }
+                    None => text.push_str(RESET),
+                }
+                current = colour;
+            }
+            text.push(glyph);
+        }
+
+        if current.is_some() {
+            text.push_str(RESET);
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+const RESET: &str = "\x1b[0m";
+
+// picks the line which joins up with the walls above, right, below and left
+fn box_wall(board: &[Vec<Tile>], x: usize, y: usize) -> char {
+    let wall = |x: Option<usize>, y: Option<usize>| match (x, y) {
+        (Some(x), Some(y)) => board.get(y).and_then(|row| row.get(x)) == Some(&Tile::Wall),
+        _ => false,
+    };
+
+    let up = wall(Some(x), y.checked_sub(1));
+    let right = wall(x.checked_add(1), Some(y));
+    let down = wall(Some(x), y.checked_add(1));
+    let left = wall(x.checked_sub(1), Some(y));
+
+    match (up, right, down, left) {
+        (false, false, false, false) => '■',
+        (_, false, _, false) => '│',
+        (false, _, false, _) => '─',
+        (true, true, false, false) => '└',
+        (false, true, true, false) => '┌',
+        (false, false, true, true) => '┐',
+        (true, false, false, true) => '┘',
+        (true, true, true, false) => '├',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┤',
+        (true, true, false, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::Level;
+    use crate::room::Room;
+    use crate::text::{to_text, Glyphs, GLYPH_PRESETS};
+    use crate::theme::Theme;
+    use crate::tile::Tile;
+
+    fn walled_room() -> Level {
+        let mut level = Level::new(5, 4, "text", 3, 2);
+        level.add_room(&Room::new(1, 1, 3, 2, None));
+        level.add_walls();
+        level.board[1][1] = Tile::StairsUp;
+        level
+    }
+
+    #[test]
+    fn test_classic() {
+        let text = to_text(&walled_room(), &Glyphs::default(), None);
+
+        assert_eq!(text, "#####\n#<..#\n#...#\n#####\n");
+    }
+
+    #[test]
+    fn test_box_walls() {
+        let glyphs = Glyphs::preset("box").unwrap();
+        let text = to_text(&walled_room(), &glyphs, None);
+
+        assert_eq!(text, "┌───┐\n│<..│\n│...│\n└───┘\n");
+    }
+
+    #[test]
+    fn test_colour() {
+        let text = to_text(&walled_room(), &Glyphs::default(), Some(&Theme::default()));
+
+        // one code for each run of the same colour, reset at the end of each line
+        assert!(text.starts_with("\x1b[38;2;244;134;66m#####\x1b[0m\n"));
+        assert!(text.contains("\x1b[38;2;52;168;83m<\x1b[38;2;66;134;244m.."));
+    }
+
+    #[test]
+    fn test_presets() {
+        for name in GLYPH_PRESETS.iter() {
+            assert!(Glyphs::preset(name).is_some());
+        }
+        assert_eq!(Glyphs::find("digits").unwrap().tile(Tile::Wall), '2');
+        assert_eq!(Glyphs::find("glyphs/blocks.json").unwrap().wall, '█');
+        assert!(Glyphs::find("glyphs/none.json").is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use serde::de::Error;
+use serde::{Deserializer, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::from_str;
+use std::fs;
+
+use crate::roles::RoomRole;
+use crate::tile::Tile;
+
+// An RGB colour, written as a hex string such as "#4286f4"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Colour {
+    pub const fn new(r: u8, g: u8, b: u8) -> Colour {
+        Colour { r, g, b }
+    }
+
+    // takes "#rrggbb" or "rrggbb"
+    pub fn from_hex(hex: &str) -> Option<Colour> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Colour::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    // 0.0 - 1.0 for each channel, as Cairo uses
+    pub fn rgb(&self) -> (f64, f64, f64) {
+        (
+            self.r as f64 / 255.0,
+            self.g as f64 / 255.0,
+            self.b as f64 / 255.0,
+        )
+    }
+}
+
+// the serde traits are named in full, as with serde's `derive` feature on their names
+// clash with the derive macros
+impl serde::Serialize for Colour {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.hex())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Colour {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex: String = serde::Deserialize::deserialize(deserializer)?;
+        Colour::from_hex(&hex)
+            .ok_or_else(|| D::Error::custom(format!("invalid colour {}, expected #rrggbb", hex)))
+    }
+}
+
+// Colours and sizes used when drawing a level. Settings missing from a theme file
+// are taken from the classic theme.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    // left transparent if unset
+    pub background: Option<Colour>,
+    pub floor: Colour,
+    pub wall: Colour,
+    pub stairs_up: Colour,
+    pub stairs_down: Colour,
+    pub door: Colour,
+    pub secret_door: Colour,
+    // room floor, for rooms with a role
+    pub entrance: Colour,
+    pub boss: Colour,
+    pub treasure: Colour,
+    pub secret: Colour,
+    pub key: Colour,
+    // lines between every tile, if set
+    pub grid: Option<Colour>,
+    pub grid_width: f64,
+    // line round each room, if set
+    pub outline: Option<Colour>,
+    pub outline_width: f64,
+    // BSP partition lines, for levels which keep their tree
+    pub tree: Colour,
+    // pixels per tile in images; the level's own tile size if unset
+    pub tile_size: Option<i32>,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            background: None,
+            floor: Colour::new(66, 134, 244),
+            wall: Colour::new(244, 134, 66),
+            stairs_up: Colour::new(52, 168, 83),
+            stairs_down: Colour::new(234, 67, 53),
+            door: Colour::new(118, 74, 36),
+            secret_door: Colour::new(138, 88, 189),
+            entrance: Colour::new(52, 168, 83),
+            boss: Colour::new(234, 67, 53),
+            treasure: Colour::new(251, 188, 4),
+            secret: Colour::new(138, 88, 189),
+            key: Colour::new(251, 188, 4),
+            grid: None,
+            grid_width: 1.0,
+            outline: None,
+            outline_width: 2.0,
+            tree: Colour::new(255, 0, 144),
+            tile_size: None,
+        }
+    }
+}
+
+pub const PRESETS: [&str; 4] = ["classic", "parchment", "dark", "blueprint"];
+
+impl Theme {
+    pub fn preset(name: &str) -> Option<Theme> {
+        let theme = match name {
+            "classic" => Theme::default(),
+            "parchment" => Theme {
+                background: Some(Colour::new(244, 236, 216)),
+                floor: Colour::new(232, 217, 181),
+                wall: Colour::new(91, 70, 54),
+                stairs_up: Colour::new(107, 142, 35),
+                stairs_down: Colour::new(165, 42, 42),
+                door: Colour::new(139, 90, 43),
+                secret_door: Colour::new(112, 84, 124),
+                entrance: Colour::new(214, 224, 170),
+                boss: Colour::new(222, 170, 150),
+                treasure: Colour::new(240, 210, 120),
+                secret: Colour::new(205, 190, 210),
+                key: Colour::new(184, 134, 11),
+                grid: Some(Colour::new(200, 180, 138)),
+                outline: Some(Colour::new(91, 70, 54)),
+                ..Theme::default()
+            },
+            "dark" => Theme {
+                background: Some(Colour::new(30, 30, 30)),
+                floor: Colour::new(70, 70, 70),
+                wall: Colour::new(140, 140, 140),
+                stairs_up: Colour::new(76, 175, 80),
+                stairs_down: Colour::new(229, 57, 53),
+                door: Colour::new(161, 136, 127),
+                secret_door: Colour::new(149, 117, 205),
+                entrance: Colour::new(56, 94, 60),
+                boss: Colour::new(110, 50, 50),
+                treasure: Colour::new(120, 100, 40),
+                secret: Colour::new(80, 64, 110),
+                key: Colour::new(255, 214, 0),
+                ..Theme::default()
+            },
+            "blueprint" => Theme {
+                background: Some(Colour::new(31, 78, 121)),
+                floor: Colour::new(46, 109, 164),
+                wall: Colour::new(255, 255, 255),
+                stairs_up: Colour::new(170, 220, 255),
+                stairs_down: Colour::new(240, 200, 90),
+                door: Colour::new(200, 230, 255),
+                secret_door: Colour::new(120, 170, 220),
+                entrance: Colour::new(46, 109, 164),
+                boss: Colour::new(46, 109, 164),
+                treasure: Colour::new(46, 109, 164),
+                secret: Colour::new(46, 109, 164),
+                key: Colour::new(255, 255, 255),
+                grid: Some(Colour::new(63, 127, 191)),
+                outline: Some(Colour::new(255, 255, 255)),
+                ..Theme::default()
+            },
+            _ => return None,
+        };
+
+        Some(theme)
+    }
+
+    pub fn load(path: &str) -> std::io::Result<Theme> {
+        let theme = fs::read_to_string(path)?;
+        Ok(from_str(&theme)?)
+    }
+
+    // a preset, or a theme file if there's no preset with that name
+    pub fn find(name: &str) -> std::io::Result<Theme> {
+        match Theme::preset(name) {
+            Some(theme) => Ok(theme),
+            None => Theme::load(name),
+        }
+    }
+
+    pub fn tile(&self, tile: Tile) -> Option<Colour> {
+        match tile {
+            Tile::Empty => None,
+            Tile::Walkable => Some(self.floor),
+            Tile::Wall => Some(self.wall),
+            Tile::StairsUp => Some(self.stairs_up),
+            Tile::StairsDown => Some(self.stairs_down),
+            Tile::Door => Some(self.door),
+            Tile::SecretDoor => Some(self.secret_door),
+        }
+    }
+
+    // floor colour for rooms with a role
+    pub fn role(&self, role: RoomRole) -> Option<Colour> {
+        match role {
+            RoomRole::Normal => None,
+            RoomRole::Entrance => Some(self.entrance),
+            RoomRole::Boss => Some(self.boss),
+            RoomRole::Treasure => Some(self.treasure),
+            RoomRole::Secret => Some(self.secret),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::from_str;
+
+    use crate::theme::{Colour, Theme, PRESETS};
+
+    #[test]
+    fn test_colour_hex() {
+        assert_eq!(Colour::from_hex("#4286f4"), Some(Colour::new(66, 134, 244)));
+        assert_eq!(Colour::from_hex("4286F4"), Some(Colour::new(66, 134, 244)));
+        assert_eq!(Colour::from_hex("#4286f"), None);
+        assert_eq!(Colour::new(66, 134, 244).hex(), "#4286f4");
+    }
+
+    #[test]
+    fn test_theme_file() {
+        let theme: Theme =
+            from_str(r##"{ "background": "#000000", "wall": "#ffffff", "tile_size": 8 }"##)
+                .unwrap();
+
+        assert_eq!(theme.background, Some(Colour::new(0, 0, 0)));
+        assert_eq!(theme.wall, Colour::new(255, 255, 255));
+        assert_eq!(theme.tile_size, Some(8));
+        assert_eq!(theme.floor, Theme::default().floor);
+        assert!(from_str::<Theme>(r#"{ "wall": "white" }"#).is_err());
+    }
+
+    #[test]
+    fn test_presets() {
+        for name in PRESETS.iter() {
+            assert!(Theme::preset(name).is_some());
+        }
+        assert!(Theme::find("themes/ink.json").is_ok());
+        assert!(Theme::find("themes/none.json").is_err());
+    }
+}
@@ -5,26 +5,59 @@ use std::fmt;
 pub enum Tile {
     Empty,
     Walkable,
-    Wall
+    Wall,
+    StairsUp,
+    StairsDown,
+    Door,
+    SecretDoor
 }
 
+impl Tile {
+    pub fn is_walkable(&self) -> bool {
+        matches!(self, Tile::Walkable | Tile::StairsUp | Tile::StairsDown | Tile::Door | Tile::SecretDoor)
+    }
+
+    // the number used for the tile in JSON, CSV and text output
+    pub fn id(&self) -> i32 {
+        match self {
+            Tile::Empty => 0,
+            Tile::Walkable => 1,
+            Tile::Wall => 2,
+            Tile::StairsUp => 3,
+            Tile::StairsDown => 4,
+            Tile::Door => 5,
+            Tile::SecretDoor => 6
+        }
+    }
+
+    pub fn from_id(id: i32) -> Option<Tile> {
+        TILES.iter().copied().find(|tile| tile.id() == id)
+    }
+}
+
+pub const TILES: [Tile; 7] = [
+    Tile::Empty,
+    Tile::Walkable,
+    Tile::Wall,
+    Tile::StairsUp,
+    Tile::StairsDown,
+    Tile::Door,
+    Tile::SecretDoor
+];
+
 impl fmt::Display for Tile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // empty tiles are left blank so the level's shape stands out
         match self {
             Tile::Empty => write!(f, " "),
-            Tile::Walkable => write!(f, "1"),
-            Tile::Wall => write!(f, "2")
+            _ => write!(f, "{}", self.id())
         }
     }
 }
 
 impl Serialize for Tile {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        match self {
-            Tile::Empty => serializer.serialize_i32(0),
-            Tile::Walkable => serializer.serialize_i32(1),
-            Tile::Wall => serializer.serialize_i32(2)
-        }
+        serializer.serialize_i32(self.id())
     }
 }
 
@@ -32,19 +65,13 @@ impl<'de> Deserialize<'de> for Tile {
   fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where D: Deserializer<'de> {
       let s = i32::deserialize(deserializer)?;
-      let tile = match s {
-        0 => Tile::Empty,
-        1 => Tile::Walkable,
-        2 => Tile::Wall,
-        _ => Tile::Empty
-      };
-      Ok(tile)
+      Ok(Tile::from_id(s).unwrap_or(Tile::Empty))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tile::Tile;
+    use crate::tile::{Tile, TILES};
     use serde_json;
 
     #[test]
@@ -57,5 +84,26 @@ mod tests {
     fn test_tile_serialise() {
         assert_eq!(serde_json::to_string(&Tile::Empty).unwrap(), "0");
         assert_eq!(serde_json::to_string(&Tile::Walkable).unwrap(), "1");
+        assert_eq!(serde_json::to_string(&Tile::StairsDown).unwrap(), "4");
+    }
+
+    // text, CSV and JSON all use the same number for each tile
+    #[test]
+    fn test_encodings_match() {
+        for tile in TILES.iter() {
+            let json = serde_json::to_string(tile).unwrap();
+            assert_eq!(json, tile.id().to_string());
+            assert_eq!(serde_json::from_str::<Tile>(&json).unwrap(), *tile);
+            assert_eq!(Tile::from_id(tile.id()), Some(*tile));
+            if *tile != Tile::Empty {
+                assert_eq!(tile.to_string(), json);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tile_deserialise() {
+        assert_eq!(serde_json::from_str::<Tile>("3").unwrap(), Tile::StairsUp);
+        assert_eq!(serde_json::from_str::<Tile>("9").unwrap(), Tile::Empty);
     }
 }
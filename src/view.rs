@@ -0,0 +1,378 @@
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+use std::fs;
+use std::io::{self, Write};
+use std::panic;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::draw::save;
+use crate::dungeon::Algorithm;
+use crate::level::Level;
+use crate::output::FormatOptions;
+use crate::seed::Seed;
+use crate::text::{to_text, Glyphs, GLYPH_PRESETS};
+
+const HELP: &str = "n/p next/previous seed  w walls  a algorithm  r roles  l locks  d prune  \
+                    arrows size  g glyphs  c colour  s save  q quit";
+
+// where `s` saves to
+const SAVE_DIR: &str = "saved";
+
+// The level being looked at, and the settings it was made with. `next` steps through
+// the seeds a batch made from the starting seed would use, so a level found here can
+// be found in the batch too.
+struct Viewer {
+    config: Config,
+    seed: Seed,
+    index: Option<usize>,
+    message: Option<String>,
+}
+
+impl Viewer {
+    fn seed(&self) -> Seed {
+        match self.index {
+            Some(index) => self.seed.derive(index),
+            None => self.seed.clone(),
+        }
+    }
+
+    fn build(&self) -> Result<Level, String> {
+        let builder = self.config.builder().map_err(|err| err.to_string())?;
+        builder
+            .seed(self.seed())
+            .build()
+            .map_err(|err| err.to_string())
+    }
+
+    // changes the settings for a key, returning false to quit
+    fn press(&mut self, key: KeyEvent) -> bool {
+        self.message = None;
+        // raw mode turns ctrl-c into a key press rather than a signal
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return !matches!(key.code, KeyCode::Char('c'));
+        }
+
+        let config = &mut self.config;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('n') | KeyCode::Char(' ') => {
+                self.index = Some(self.index.map_or(0, |index| index + 1));
+            }
+            KeyCode::Char('p') => {
+                self.index = match self.index {
+                    Some(0) | None => None,
+                    Some(index) => Some(index - 1),
+                };
+            }
+            KeyCode::Char('w') => config.walls = !config.walls,
+            KeyCode::Char('a') => {
+                config.algorithm = match config.algorithm {
+                    Algorithm::Rooms => Algorithm::Bsp,
+                    Algorithm::Bsp => Algorithm::Rooms,
+                };
+            }
+            KeyCode::Char('r') => config.roles = !config.roles,
+            KeyCode::Char('l') => config.locks = (config.locks + 1) % 4,
+            KeyCode::Char('d') => {
+                config.prune = match config.prune {
+                    Some(_) => None,
+                    None => Some(0.0),
+                };
+            }
+            KeyCode::Left => config.width = (config.width - 2).max(MIN_SIZE),
+            KeyCode::Right => config.width += 2,
+            KeyCode::Up => config.height = (config.height - 2).max(MIN_SIZE),
+            KeyCode::Down => config.height += 2,
+            KeyCode::Char('g') => {
+                let current = config.glyphs.as_deref().unwrap_or(GLYPH_PRESETS[0]);
+                let next = match GLYPH_PRESETS.iter().position(|&name| name == current) {
+                    Some(index) => GLYPH_PRESETS[(index + 1) % GLYPH_PRESETS.len()],
+                    None => GLYPH_PRESETS[0],
+                };
+                config.glyphs = Some(next.to_string());
+            }
+            KeyCode::Char('c') => config.colour = !config.colour,
+            KeyCode::Char('s') => {
+                self.message = Some(match self.save() {
+                    Ok(path) => format!("Saved to {}", path),
+                    Err(err) => format!("Couldn't save: {}", err),
+                });
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    // writes the level in the output format, as a png, and the settings used as a preset
+    fn save(&self) -> io::Result<String> {
+        let level = self.build().map_err(io::Error::other)?;
+        let seed = self.seed();
+        let dir = Path::new(SAVE_DIR);
+        fs::create_dir_all(dir)?;
+
+        let mut config = self.config.clone();
+        config.seed = Some(seed.to_string());
+        config.text = None;
+        let document = config.format.level(&level, &config.format_options()?)?;
+        fs::write(
+            dir.join(format!("{}.{}", seed, config.format.extension())),
+            document,
+        )?;
+        save(&level, &config.theme()?, &dir.join(format!("{}.png", seed)))?;
+        fs::write(
+            dir.join(format!("{}-config.json", seed)),
+            serde_json::to_string_pretty(&config)?,
+        )?;
+
+        Ok(dir.join(seed.to_string()).display().to_string())
+    }
+
+    fn status(&self, level: &Result<Level, String>) -> Vec<String> {
+        let config = &self.config;
+        let seed = match self.index {
+            Some(index) => format!(
+                "{} (level {} of a batch from {})",
+                self.seed(),
+                index,
+                self.seed
+            ),
+            None => self.seed.to_string(),
+        };
+        let settings = format!(
+            "{:?} {}x{}  walls {}  roles {}  locks {}  prune {}",
+            config.algorithm,
+            config.width,
+            config.height,
+            on_off(config.walls),
+            on_off(config.roles),
+            config.locks,
+            on_off(config.prune.is_some())
+        );
+        let stats = match level {
+            Ok(level) => {
+                let stats = level.stats();
+                format!(
+                    "rooms {}  corridors {}  floor {}  dead ends {}  entities {}  locks {}",
+                    stats.rooms,
+                    stats.corridors,
+                    stats.floor_tiles,
+                    stats.dead_ends,
+                    stats.entities,
+                    stats.locks
+                )
+            }
+            Err(err) => format!("Couldn't create level: {}", err),
+        };
+
+        vec![
+            format!("Seed: {}", seed),
+            settings,
+            stats,
+            self.message.clone().unwrap_or_default(),
+            HELP.to_string(),
+        ]
+    }
+
+    fn draw<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let level = self.build();
+        let (columns, rows) = size()?;
+        let options = self
+            .config
+            .format_options()
+            .unwrap_or_else(|_| FormatOptions {
+                glyphs: Some(Glyphs::default()),
+                ..FormatOptions::default()
+            });
+        let glyphs = options.glyphs.unwrap_or_default();
+        let map = match &level {
+            Ok(level) => to_text(level, &glyphs, options.colours.as_ref()),
+            Err(_) => String::new(),
+        };
+        let status = self.status(&level);
+
+        // keeps the status lines on screen, cutting off the bottom of the map if need be
+        let map_rows = (rows as usize).saturating_sub(status.len() + 1);
+        queue!(out, Clear(ClearType::All))?;
+        for (row, line) in map.lines().take(map_rows).enumerate() {
+            queue!(
+                out,
+                MoveTo(0, row as u16),
+                Print(crop(line, columns as usize))
+            )?;
+        }
+        let top = map.lines().count().min(map_rows) + 1;
+        for (row, line) in status.iter().enumerate() {
+            queue!(
+                out,
+                MoveTo(0, (top + row) as u16),
+                Print(crop(line, columns as usize))
+            )?;
+        }
+
+        out.flush()
+    }
+}
+
+const MIN_SIZE: i32 = 10;
+
+fn on_off(on: bool) -> &'static str {
+    match on {
+        true => "on",
+        false => "off",
+    }
+}
+
+// cuts a line to the width of the terminal, leaving colour codes alone
+fn crop(line: &str, columns: usize) -> String {
+    let mut cropped = String::new();
+    let mut shown = 0;
+    let mut escape = false;
+    for c in line.chars() {
+        if c == '\x1b' {
+            escape = true;
+        }
+        if escape || shown < columns {
+            cropped.push(c);
+        }
+        if escape {
+            escape = c != 'm';
+        } else {
+            shown += 1;
+        }
+    }
+
+    cropped
+}
+
+// puts the terminal back how it was
+fn restore<W: Write>(out: &mut W) -> io::Result<()> {
+    execute!(out, Show, LeaveAlternateScreen)?;
+    disable_raw_mode()
+}
+
+// The viewer generates one level at a time, so can't show the other floors of a dungeon
+// or a level read from a file.
+fn check(config: &Config) -> io::Result<()> {
+    let unsupported = |message: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    if config.floors > 1 {
+        return unsupported("the viewer only shows one floor, so floors can't be more than 1");
+    }
+    if config.import.is_some() {
+        return unsupported("the viewer can't show imported levels");
+    }
+
+    Ok(())
+}
+
+// Shows the level made with the config and seed, and waits for keys until `q` is pressed.
+// The terminal is put back how it was even if drawing fails or something panics.
+pub fn run(config: Config, seed: Seed) -> io::Result<()> {
+    check(&config)?;
+    let mut viewer = Viewer {
+        config,
+        seed,
+        index: None,
+        message: None,
+    };
+    let mut out = io::stdout();
+
+    // the panic message is printed after the terminal is restored, so it can be read
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore(&mut io::stdout());
+        hook(info);
+    }));
+
+    enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+    let result = (|| loop {
+        viewer.draw(&mut out)?;
+        if let Event::Key(key) = read()? {
+            if key.kind != KeyEventKind::Release && !viewer.press(key) {
+                return Ok(());
+            }
+        }
+    })();
+    restore(&mut out)?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use crate::config::Config;
+    use crate::dungeon::Algorithm;
+    use crate::seed::Seed;
+    use crate::view::{check, crop, Viewer};
+
+    #[test]
+    fn test_keys() {
+        let seed = Seed::new("view");
+        let mut viewer = Viewer {
+            config: Config::default(),
+            seed: seed.clone(),
+            index: None,
+            message: None,
+        };
+
+        assert!(viewer.press(KeyCode::Char('n').into()));
+        assert!(viewer.press(KeyCode::Char('n').into()));
+        assert_eq!(viewer.seed(), seed.derive(1));
+        viewer.press(KeyCode::Char('p').into());
+        viewer.press(KeyCode::Char('p').into());
+        assert_eq!(viewer.seed(), seed);
+
+        viewer.press(KeyCode::Char('w').into());
+        viewer.press(KeyCode::Char('a').into());
+        viewer.press(KeyCode::Left.into());
+        viewer.press(KeyCode::Char('g').into());
+        assert!(viewer.config.walls);
+        assert_eq!(viewer.config.algorithm, Algorithm::Bsp);
+        assert_eq!(viewer.config.width, 46);
+        assert_eq!(viewer.config.glyphs.as_deref(), Some("box"));
+        assert!(viewer.build().is_ok());
+
+        assert!(!viewer.press(KeyCode::Char('q').into()));
+
+        // ctrl-c quits rather than turning colour on
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert!(!viewer.press(ctrl_c));
+        assert!(!viewer.config.colour);
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check(&Config::default()).is_ok());
+
+        let floors = Config {
+            floors: 2,
+            ..Config::default()
+        };
+        assert!(check(&floors).is_err());
+
+        let import = Config {
+            import: Some("sketch.png".to_string()),
+            ..Config::default()
+        };
+        assert!(check(&import).is_err());
+    }
+
+    #[test]
+    fn test_crop() {
+        assert_eq!(crop("#####", 3), "###");
+        assert_eq!(
+            crop("\x1b[38;2;1;2;3m####\x1b[0m", 2),
+            "\x1b[38;2;1;2;3m##\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use wasm_bindgen::prelude::*;
+
+use crate::config::Config;
+use crate::output::{FormatOptions, OutputFormat};
+use crate::seed::Seed;
+
+// Generates a level in the browser from settings given as a JSON object, the same as a
+// config file, eg `{"seed": "hello", "algorithm": "bsp", "walls": true}`. Settings left
+// out use the defaults, and a random seed is used if none is set.
+//
+// Returns the level as JSON, the same as `--output-format json`, or the whole dungeon if
+// there's more than one floor. Settings which read or write files don't work here.
+#[wasm_bindgen]
+pub fn generate(settings: &str) -> Result<String, JsValue> {
+    let error = |err: String| JsValue::from_str(&err);
+    let config: Config = match settings.trim() {
+        "" => Config::default(),
+        settings => serde_json::from_str(settings).map_err(|err| error(err.to_string()))?,
+    };
+
+    let seed = config.seed().unwrap_or_else(Seed::random);
+    let dungeon = config
+        .builder()
+        .map_err(|err| error(err.to_string()))?
+        .seed(seed)
+        .build_dungeon()
+        .map_err(|err| error(err.to_string()))?;
+
+    OutputFormat::Json
+        .dungeon(&dungeon, &FormatOptions::default())
+        .map_err(|err| error(err.to_string()))
+}
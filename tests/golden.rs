@@ -0,0 +1,122 @@
+// Generates levels for fixed seeds and compares the boards against checked-in fixtures,
+// so a change which alters levels people have already shared doesn't go unnoticed.
+// If a change is meant to alter levels, rewrite the fixtures with
+// `BLESS=1 cargo test --test golden` and check the diff.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use dungeon::dungeon::Algorithm;
+use dungeon::seed::Seed;
+
+struct Case {
+    seed: &'static str,
+    algorithm: Algorithm,
+    width: i32,
+    height: i32,
+    walls: bool,
+    min_room_width: i32,
+    min_room_height: i32,
+}
+
+impl Case {
+    fn name(&self) -> String {
+        let algorithm = match self.algorithm {
+            Algorithm::Rooms => "rooms",
+            Algorithm::Bsp => "bsp",
+        };
+
+        format!(
+            "{}-{}-{}x{}-{}x{}{}",
+            algorithm,
+            self.seed,
+            self.width,
+            self.height,
+            self.min_room_width,
+            self.min_room_height,
+            if self.walls { "-walls" } else { "" }
+        )
+    }
+
+    fn board(&self) -> String {
+        let seed = Seed::new(self.seed);
+        let level = self.algorithm.create(
+            self.width,
+            self.height,
+            seed.hash(),
+            &mut seed.rng(),
+            self.walls,
+            self.min_room_width,
+            self.min_room_height,
+        );
+
+        level.board_to_csv() + "\n"
+    }
+}
+
+fn cases() -> Vec<Case> {
+    let mut cases = vec![];
+    for &algorithm in &[Algorithm::Rooms, Algorithm::Bsp] {
+        for &seed in &["hello", "42", "golden"] {
+            cases.push(Case {
+                seed,
+                algorithm,
+                width: 48,
+                height: 40,
+                walls: true,
+                min_room_width: 4,
+                min_room_height: 5,
+            });
+        }
+
+        cases.push(Case {
+            seed: "small",
+            algorithm,
+            width: 30,
+            height: 24,
+            walls: false,
+            min_room_width: 3,
+            min_room_height: 3,
+        });
+        cases.push(Case {
+            seed: "large",
+            algorithm,
+            width: 80,
+            height: 60,
+            walls: true,
+            min_room_width: 5,
+            min_room_height: 6,
+        });
+    }
+
+    cases
+}
+
+#[test]
+fn test_golden_boards() {
+    let bless = env::var("BLESS").is_ok();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut failures = vec![];
+
+    for case in cases() {
+        let path = dir.join(format!("{}.csv", case.name()));
+        let board = case.board();
+
+        if bless {
+            fs::write(&path, &board).expect("Couldn't write fixture");
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(expected) if expected == board => (),
+            Ok(_) => failures.push(format!("{} has changed", case.name())),
+            Err(_) => failures.push(format!("{} has no fixture", case.name())),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{}\nIf this is expected, run `BLESS=1 cargo test --test golden` to update the fixtures",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,91 @@
+// Generates levels for thousands of seeds and sizes, checking each one is usable.
+use rand::Rng;
+
+use dungeon::dungeon::Algorithm;
+use dungeon::locks::{add_locks, is_solvable};
+use dungeon::roles::assign_roles;
+use dungeon::seed::Seed;
+
+use std::env;
+
+// per generator, `INVARIANT_LEVELS=20000 cargo test --release --test invariants` for a longer run
+const LEVELS: u64 = 1000;
+
+fn levels() -> u64 {
+    env::var("INVARIANT_LEVELS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(LEVELS)
+}
+
+struct Params {
+    width: i32,
+    height: i32,
+    min_room_width: i32,
+    min_room_height: i32,
+    walls: bool,
+}
+
+// sizes from tiny to large, with rooms from single tiles to bigger than the level
+fn params(seed: &Seed) -> Params {
+    let mut rng = seed.derive(0).rng();
+    Params {
+        width: rng.gen_range(8..=120),
+        height: rng.gen_range(8..=120),
+        min_room_width: rng.gen_range(1..=12),
+        min_room_height: rng.gen_range(1..=14),
+        walls: rng.gen(),
+    }
+}
+
+fn check(algorithm: Algorithm) {
+    for i in 0..levels() {
+        let seed = Seed::from(i);
+        let params = params(&seed);
+        let mut rng = seed.rng();
+        let mut level = algorithm.create(
+            params.width,
+            params.height,
+            seed.hash(),
+            &mut rng,
+            params.walls,
+            params.min_room_width,
+            params.min_room_height,
+        );
+
+        let describe = format!(
+            "{:?} seed {} at {}x{}, rooms from {}x{}",
+            algorithm,
+            i,
+            params.width,
+            params.height,
+            params.min_room_width,
+            params.min_room_height
+        );
+
+        assert!(!level.rooms.is_empty(), "{} has no rooms", describe);
+        if let Err(e) = level.validate() {
+            panic!("{}: {}", describe, e);
+        }
+
+        // post-processing shouldn't break anything either
+        level.prune_dead_ends(0.2, &mut rng);
+        assign_roles(&mut level, &mut rng);
+        add_locks(&mut level, 2, &mut rng);
+
+        if let Err(e) = level.validate() {
+            panic!("{} after post-processing: {}", describe, e);
+        }
+        assert!(is_solvable(&level), "{} can't be solved", describe);
+    }
+}
+
+#[test]
+fn test_rooms_corridors_invariants() {
+    check(Algorithm::Rooms);
+}
+
+#[test]
+fn test_bsp_invariants() {
+    check(Algorithm::Bsp);
+}
@@ -0,0 +1,41 @@
+// Runs in a headless wasm runtime with
+// `cargo test --target wasm32-unknown-unknown --no-default-features --test wasm`,
+// checking the browser makes the same levels as the command line.
+#![cfg(target_arch = "wasm32")]
+use serde_json::Value;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use dungeon::csv::{board_to_csv, CsvOptions};
+use dungeon::tile::Tile;
+use dungeon::wasm::generate;
+
+fn board(json: &str) -> String {
+    let level: Value = serde_json::from_str(json).unwrap();
+    let board: Vec<Vec<Tile>> = serde_json::from_value(level["board"].clone()).unwrap();
+    board_to_csv(&board, &CsvOptions::default()) + "\n"
+}
+
+#[wasm_bindgen_test]
+fn test_matches_fixtures() {
+    let rooms = generate(r#"{"seed": "hello", "walls": true}"#).unwrap();
+    let bsp = generate(r#"{"seed": "hello", "algorithm": "bsp", "walls": true}"#).unwrap();
+
+    assert_eq!(
+        board(&rooms),
+        include_str!("fixtures/rooms-hello-48x40-4x5-walls.csv")
+    );
+    assert_eq!(
+        board(&bsp),
+        include_str!("fixtures/bsp-hello-48x40-4x5-walls.csv")
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_settings() {
+    let dungeon: Value = serde_json::from_str(&generate(r#"{"floors": 2}"#).unwrap()).unwrap();
+
+    assert_eq!(dungeon["floors"].as_array().unwrap().len(), 2);
+    assert!(generate("").is_ok());
+    assert!(generate(r#"{"width": 0}"#).is_err());
+    assert!(generate(r#"{"size": 10}"#).is_err());
+}